@@ -0,0 +1,967 @@
+//! Token sources for hubs, and the `GetToken` trait they implement.
+use std::collections::HashMap;
+use std::env;
+use std::error;
+use std::fmt::{self, Display};
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use hyper::client::connect::HttpConnector;
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper_rustls::HttpsConnector;
+
+use serde_json as json;
+
+use crate::{https_client, HttpsClient};
+
+/// The future returned by `GetToken::token()`.
+pub type TokenFuture<'a> = Pin<
+    Box<dyn Future<Output = std::result::Result<oauth2::AccessToken, oauth2::Error>> + Send + 'a>,
+>;
+
+/// A source of access tokens, as used by the hub to authorize each request.
+///
+/// It is implemented by the `yup_oauth2` authenticators, as well as by the token sources
+/// of this module, which allows them to be used interchangeably.
+pub trait GetToken: Send + Sync {
+    /// Return a token valid for the given scopes, fetching or refreshing it if needed.
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> TokenFuture<'a>;
+
+    /// Return the API key to use for methods which don't require a token, if there is one.
+    fn api_key(&self) -> Option<String> {
+        None
+    }
+
+    /// Called by the hub with the root url of its API, whenever it is set.
+    /// It allows token sources to obtain tokens for exactly this API.
+    fn set_root_url(&mut self, root_url: &str) {
+        let _ = root_url;
+    }
+}
+
+impl GetToken for oauth2::authenticator::Authenticator<HttpsConnector<HttpConnector>> {
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> TokenFuture<'a> {
+        Box::pin(oauth2::authenticator::Authenticator::token(self, scopes))
+    }
+}
+
+impl<T: GetToken + ?Sized> GetToken for Box<T> {
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> TokenFuture<'a> {
+        (**self).token(scopes)
+    }
+
+    fn api_key(&self) -> Option<String> {
+        (**self).api_key()
+    }
+
+    fn set_root_url(&mut self, root_url: &str) {
+        (**self).set_root_url(root_url)
+    }
+}
+
+/// Create an access token from its parts, which `yup_oauth2` has no public constructor for.
+pub fn access_token(value: String, expires_at: Option<DateTime<Utc>>) -> oauth2::AccessToken {
+    json::from_value(json::json!({
+        "value": value,
+        "expires_at": expires_at,
+    }))
+    .expect("an access token to be constructible from its fields")
+}
+
+/// Keeps access tokens per set of scopes until they are about to expire.
+#[derive(Default)]
+pub struct TokenCache {
+    tokens: Mutex<HashMap<Vec<String>, oauth2::AccessToken>>,
+}
+
+impl TokenCache {
+    fn key(scopes: &[&str]) -> Vec<String> {
+        let mut key: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+        key.sort();
+        key.dedup();
+        key
+    }
+
+    /// Return the token stored for the given scopes, unless it is expired.
+    pub fn get(&self, scopes: &[&str]) -> Option<oauth2::AccessToken> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .get(&Self::key(scopes))
+            .filter(|t| !t.is_expired())
+            .cloned()
+    }
+
+    /// Store the token for the given scopes, replacing any previous one.
+    pub fn set(&self, scopes: &[&str], token: oauth2::AccessToken) {
+        self.tokens.lock().unwrap().insert(Self::key(scopes), token);
+    }
+}
+
+/// The response of an OAuth 2.0 token endpoint, or the metadata server.
+#[derive(Deserialize)]
+pub struct TokenResponse {
+    pub access_token: Option<String>,
+    pub id_token: Option<String>,
+    pub expires_in: Option<i64>,
+}
+
+impl TokenResponse {
+    /// Convert this response into an access token, failing if it didn't contain one.
+    pub fn into_access_token(self) -> std::result::Result<oauth2::AccessToken, oauth2::Error> {
+        match self.access_token {
+            Some(value) => Ok(access_token(
+                value,
+                self.expires_in
+                    .map(|secs| Utc::now() + chrono::Duration::seconds(secs)),
+            )),
+            None => Err(oauth2::Error::UserError(
+                "Token response did not contain an access_token".to_string(),
+            )),
+        }
+    }
+}
+
+/// Send the given request and return its body, mapping all failures to `oauth2::Error`.
+///
+/// Non-success responses are decoded as OAuth 2.0 error if possible.
+pub async fn fetch_token_body(
+    client: &HttpsClient,
+    req: hyper::Request<hyper::body::Body>,
+) -> std::result::Result<hyper::body::Bytes, oauth2::Error> {
+    let res = client.request(req).await?;
+    let status = res.status();
+    let body = hyper::body::to_bytes(res.into_body()).await?;
+    if !status.is_success() {
+        return Err(match json::from_slice::<oauth2::error::AuthError>(&body) {
+            Ok(err) => oauth2::Error::AuthError(err),
+            Err(_) => oauth2::Error::LowLevelError(io::Error::other(format!(
+                "{}: {}",
+                status,
+                String::from_utf8_lossy(&body)
+            ))),
+        });
+    }
+    Ok(body)
+}
+
+/// Like `fetch_token_body()`, but decodes the body from JSON.
+pub async fn fetch_token_response<T>(
+    client: &HttpsClient,
+    req: hyper::Request<hyper::body::Body>,
+) -> std::result::Result<T, oauth2::Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    Ok(json::from_slice(&fetch_token_body(client, req).await?)?)
+}
+
+/// The environment variable to override the host of the metadata server with, e.g. for testing.
+pub const METADATA_HOST_ENV: &str = "GCE_METADATA_HOST";
+
+const METADATA_HOST: &str = "metadata.google.internal";
+
+/// Obtains tokens from the metadata server available on Compute Engine, GKE, Cloud Run and
+/// other Google Cloud runtimes.
+///
+/// Tokens are cached until they are about to expire.
+pub struct MetadataServerAuthenticator {
+    client: HttpsClient,
+    root_url: String,
+    account: String,
+    cache: TokenCache,
+}
+
+impl Default for MetadataServerAuthenticator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetadataServerAuthenticator {
+    /// Use the default metadata server, or the one set in the `GCE_METADATA_HOST` environment
+    /// variable.
+    pub fn new() -> Self {
+        let host = env::var(METADATA_HOST_ENV).unwrap_or_else(|_| METADATA_HOST.to_string());
+        Self::with_root_url(format!("http://{}/computeMetadata/v1/", host))
+    }
+
+    /// Use the metadata server at the given url, like `http://localhost:8080/computeMetadata/v1/`.
+    pub fn with_root_url<S: Into<String>>(root_url: S) -> Self {
+        let mut root_url = root_url.into();
+        if !root_url.ends_with('/') {
+            root_url.push('/');
+        }
+        MetadataServerAuthenticator {
+            client: https_client(),
+            root_url,
+            account: "default".to_string(),
+            cache: Default::default(),
+        }
+    }
+
+    /// Obtain tokens for the given service account attached to the instance, instead of the
+    /// default one.
+    pub fn service_account<S: Into<String>>(mut self, email: S) -> Self {
+        self.account = email.into();
+        self
+    }
+
+    /// Perform a GET request against the metadata server for the given path, relative to the
+    /// service account's directory.
+    pub async fn get<T>(&self, path_and_query: &str) -> std::result::Result<T, oauth2::Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        Ok(json::from_slice(&self.get_body(path_and_query).await?)?)
+    }
+
+    /// Like `get()`, but returns the body as is.
+    pub async fn get_body(
+        &self,
+        path_and_query: &str,
+    ) -> std::result::Result<hyper::body::Bytes, oauth2::Error> {
+        let req = hyper::Request::get(format!(
+            "{}instance/service-accounts/{}/{}",
+            self.root_url, self.account, path_and_query
+        ))
+        .header("Metadata-Flavor", "Google")
+        .body(hyper::body::Body::empty())
+        .unwrap();
+        fetch_token_body(&self.client, req).await
+    }
+}
+
+impl GetToken for MetadataServerAuthenticator {
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> TokenFuture<'a> {
+        Box::pin(async move {
+            if let Some(token) = self.cache.get(scopes) {
+                return Ok(token);
+            }
+            let mut path = "token".to_string();
+            if !scopes.is_empty() {
+                path = url::form_urlencoded::Serializer::new(path + "?")
+                    .append_pair("scopes", &scopes.join(","))
+                    .finish();
+            }
+            let token = self
+                .get::<TokenResponse>(&path)
+                .await?
+                .into_access_token()?;
+            self.cache.set(scopes, token.clone());
+            Ok(token)
+        })
+    }
+}
+
+fn default_token_uri() -> String {
+    "https://oauth2.googleapis.com/token".to_string()
+}
+
+/// The credentials of a user, as written by `gcloud auth application-default login`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AuthorizedUserSecret {
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+    #[serde(default = "default_token_uri")]
+    pub token_uri: String,
+}
+
+/// Obtains tokens by exchanging the refresh token of an `AuthorizedUserSecret`.
+///
+/// Note that the scopes of these tokens are fixed by the refresh token.
+pub struct AuthorizedUserAuthenticator {
+    client: HttpsClient,
+    secret: AuthorizedUserSecret,
+    cache: TokenCache,
+}
+
+impl AuthorizedUserAuthenticator {
+    pub fn new(secret: AuthorizedUserSecret) -> Self {
+        AuthorizedUserAuthenticator {
+            client: https_client(),
+            secret,
+            cache: Default::default(),
+        }
+    }
+}
+
+impl GetToken for AuthorizedUserAuthenticator {
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> TokenFuture<'a> {
+        Box::pin(async move {
+            if let Some(token) = self.cache.get(scopes) {
+                return Ok(token);
+            }
+            let body = url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("grant_type", "refresh_token")
+                .append_pair("client_id", &self.secret.client_id)
+                .append_pair("client_secret", &self.secret.client_secret)
+                .append_pair("refresh_token", &self.secret.refresh_token)
+                .finish();
+            let req = hyper::Request::post(&self.secret.token_uri)
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(hyper::body::Body::from(body))
+                .unwrap();
+            let token = fetch_token_response::<TokenResponse>(&self.client, req)
+                .await?
+                .into_access_token()?;
+            self.cache.set(scopes, token.clone());
+            Ok(token)
+        })
+    }
+}
+
+/// Create an authenticator for the given service account key.
+///
+/// If `subject` is set, tokens are obtained on behalf of that user, which requires domain-wide
+/// delegation to be set up for the service account.
+pub async fn service_account_authenticator(
+    key: oauth2::ServiceAccountKey,
+    subject: Option<&str>,
+) -> io::Result<oauth2::authenticator::Authenticator<HttpsConnector<HttpConnector>>> {
+    let mut builder = oauth2::ServiceAccountAuthenticator::builder(key);
+    if let Some(subject) = subject {
+        builder = builder.subject(subject);
+    }
+    builder.build().await
+}
+
+/// Signs JSON Web Tokens with RS256, using the PKCS#8 PEM key of a service account.
+struct JwtSigner {
+    signer: Box<dyn rustls::sign::Signer>,
+    key_id: Option<String>,
+}
+
+impl JwtSigner {
+    fn new(key: &oauth2::ServiceAccountKey) -> io::Result<Self> {
+        use rustls::sign::SigningKey;
+
+        let invalid_key = |msg| io::Error::new(io::ErrorKind::InvalidInput, msg);
+        let private_key =
+            rustls::internal::pemfile::pkcs8_private_keys(&mut key.private_key.as_bytes())
+                .map_err(|_| invalid_key("Error reading key from PEM"))?
+                .into_iter()
+                .next()
+                .ok_or_else(|| invalid_key("Not enough private keys in PEM"))?;
+        let signer = rustls::sign::RSASigningKey::new(&private_key)
+            .map_err(|_| invalid_key("Couldn't initialize signer"))?
+            .choose_scheme(&[rustls::SignatureScheme::RSA_PKCS1_SHA256])
+            .ok_or_else(|| invalid_key("Couldn't choose signing scheme"))?;
+        Ok(JwtSigner {
+            signer,
+            key_id: key.private_key_id.clone(),
+        })
+    }
+
+    /// Return the signed token with the given claims.
+    fn sign(&self, claims: &json::Value) -> std::result::Result<String, oauth2::Error> {
+        let mut header = json::json!({"alg": "RS256", "typ": "JWT"});
+        if let Some(ref kid) = self.key_id {
+            header["kid"] = kid.as_str().into();
+        }
+        let mut jwt = base64::encode_config(header.to_string(), base64::URL_SAFE_NO_PAD);
+        jwt.push('.');
+        base64::encode_config_buf(claims.to_string(), base64::URL_SAFE_NO_PAD, &mut jwt);
+        let signature = self
+            .signer
+            .sign(jwt.as_bytes())
+            .map_err(|err| oauth2::Error::LowLevelError(io::Error::other(err)))?;
+        jwt.push('.');
+        base64::encode_config_buf(signature, base64::URL_SAFE_NO_PAD, &mut jwt);
+        Ok(jwt)
+    }
+}
+
+/// Uses JSON Web Tokens signed with a service account key as access tokens, which saves the
+/// round trip to the token endpoint.
+///
+/// The audience of the tokens is the one set with `audience(...)`, or else the root url of the hub it is
+/// used with, like `https://storage.googleapis.com/`. Without either, the tokens are valid for the requested
+/// scopes instead. Not all APIs accept self-signed tokens.
+///
+/// Tokens are cached until they are about to expire.
+pub struct SelfSignedJwtAuthenticator {
+    signer: JwtSigner,
+    email: String,
+    /// The root url of the hub
+    audience: Option<String>,
+    /// The audience set by the user, which takes precedence over the root url of the hub
+    audience_override: Option<String>,
+    lifetime: Duration,
+    cache: TokenCache,
+}
+
+impl SelfSignedJwtAuthenticator {
+    pub fn new(key: &oauth2::ServiceAccountKey) -> io::Result<Self> {
+        Ok(SelfSignedJwtAuthenticator {
+            signer: JwtSigner::new(key)?,
+            email: key.client_email.clone(),
+            audience: None,
+            audience_override: None,
+            lifetime: Duration::from_secs(3600),
+            cache: Default::default(),
+        })
+    }
+
+    /// Use the given audience instead of the root url of the hub.
+    pub fn audience<S: Into<String>>(mut self, audience: S) -> Self {
+        self.audience_override = Some(audience.into());
+        self.cache = Default::default();
+        self
+    }
+
+    /// Set the lifetime of each token, which defaults to one hour, the maximum.
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+}
+
+impl GetToken for SelfSignedJwtAuthenticator {
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> TokenFuture<'a> {
+        Box::pin(async move {
+            if let Some(token) = self.cache.get(scopes) {
+                return Ok(token);
+            }
+            let iat = Utc::now();
+            let exp = iat + chrono::Duration::seconds(self.lifetime.as_secs() as i64);
+            let mut claims = json::json!({
+                "iss": self.email,
+                "sub": self.email,
+                "iat": iat.timestamp(),
+                "exp": exp.timestamp(),
+            });
+            match self.audience_override.as_ref().or(self.audience.as_ref()) {
+                Some(aud) => claims["aud"] = aud.as_str().into(),
+                None => claims["scope"] = scopes.join(" ").into(),
+            }
+            let token = access_token(self.signer.sign(&claims)?, Some(exp));
+            self.cache.set(scopes, token.clone());
+            Ok(token)
+        })
+    }
+
+    fn set_root_url(&mut self, root_url: &str) {
+        if self.audience.as_deref() != Some(root_url) {
+            self.audience = Some(root_url.to_string());
+            if self.audience_override.is_none() {
+                self.cache = Default::default();
+            }
+        }
+    }
+}
+
+/// The scope needed by the source credentials of an `ImpersonatedAuthenticator`.
+pub const IAM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+const IAM_CREDENTIALS_ROOT_URL: &str = "https://iamcredentials.googleapis.com/";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateAccessTokenResponse {
+    access_token: String,
+    expire_time: DateTime<Utc>,
+}
+
+/// Obtains short-lived tokens of a target service account, using the tokens of a source
+/// authenticator which was granted the *Service Account Token Creator* role on it.
+///
+/// It calls the same `generateAccessToken` method as the `iamcredentials1` crate. If `delegates`
+/// are set, each service account in the chain must be allowed to impersonate the next one, with
+/// the last one impersonating the target.
+///
+/// Tokens are cached until they are about to expire.
+pub struct ImpersonatedAuthenticator {
+    client: HttpsClient,
+    source: Box<dyn GetToken>,
+    target: String,
+    delegates: Vec<String>,
+    lifetime: Duration,
+    root_url: String,
+    cache: TokenCache,
+}
+
+impl ImpersonatedAuthenticator {
+    /// Impersonate the service account with the given email, using the tokens of `source`.
+    pub fn new<A: 'static + GetToken, S: Into<String>>(source: A, target: S) -> Self {
+        ImpersonatedAuthenticator {
+            client: https_client(),
+            source: Box::new(source),
+            target: target.into(),
+            delegates: Vec::new(),
+            lifetime: Duration::from_secs(3600),
+            root_url: IAM_CREDENTIALS_ROOT_URL.to_string(),
+            cache: Default::default(),
+        }
+    }
+
+    /// Set the emails of the service accounts in the delegation chain, starting with the one
+    /// the source may impersonate.
+    pub fn delegates<I, S>(mut self, delegates: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.delegates = delegates.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the lifetime of obtained tokens, which defaults to one hour.
+    /// Lifetimes of up to 12 hours require the `iam.allowServiceAccountCredentialLifetimeExtension`
+    /// organization policy.
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Use the IAM credentials service at the given url, e.g. for testing.
+    pub fn with_root_url<S: Into<String>>(mut self, root_url: S) -> Self {
+        self.root_url = root_url.into();
+        if !self.root_url.ends_with('/') {
+            self.root_url.push('/');
+        }
+        self
+    }
+
+    /// Call the given method of the target service account, authorized by the source, and
+    /// decode its response.
+    async fn call<T>(
+        &self,
+        method: &str,
+        mut body: json::Value,
+    ) -> std::result::Result<T, oauth2::Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let token = self.source.token(&[IAM_SCOPE]).await?;
+        body["delegates"] = self
+            .delegates
+            .iter()
+            .map(|d| format!("projects/-/serviceAccounts/{}", d))
+            .collect();
+        let req = hyper::Request::post(format!(
+            "{}v1/projects/-/serviceAccounts/{}:{}",
+            self.root_url, self.target, method
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", token.as_str()))
+        .header(CONTENT_TYPE, "application/json")
+        .body(hyper::body::Body::from(body.to_string()))
+        .unwrap();
+        fetch_token_response(&self.client, req).await
+    }
+}
+
+impl GetToken for ImpersonatedAuthenticator {
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> TokenFuture<'a> {
+        Box::pin(async move {
+            if let Some(token) = self.cache.get(scopes) {
+                return Ok(token);
+            }
+            let res: GenerateAccessTokenResponse = self
+                .call(
+                    "generateAccessToken",
+                    json::json!({
+                        "scope": scopes,
+                        "lifetime": format!("{}s", self.lifetime.as_secs()),
+                    }),
+                )
+                .await?;
+            let token = access_token(res.access_token, Some(res.expire_time));
+            self.cache.set(scopes, token.clone());
+            Ok(token)
+        })
+    }
+}
+
+/// Return the decoded claims of the given JSON Web Token, without verifying it.
+pub fn jwt_claims(token: &str) -> Option<json::Value> {
+    let payload = token.split('.').nth(1)?;
+    json::from_slice(&base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?).ok()
+}
+
+/// Convert an ID token into an access token, expiring when the ID token does.
+fn id_token_to_access_token(token: String) -> oauth2::AccessToken {
+    use chrono::TimeZone;
+
+    let expires_at = jwt_claims(&token)
+        .and_then(|claims| claims["exp"].as_i64())
+        .and_then(|exp| Utc.timestamp_opt(exp, 0).single());
+    access_token(token, expires_at)
+}
+
+enum IdTokenSource {
+    ServiceAccount {
+        client: HttpsClient,
+        signer: JwtSigner,
+        email: String,
+        token_uri: String,
+    },
+    Metadata(MetadataServerAuthenticator),
+    Impersonated(ImpersonatedAuthenticator),
+}
+
+/// Obtains OpenID Connect ID tokens for an audience, like the url of a Cloud Run service or the
+/// OAuth client id of a resource protected by Identity-Aware Proxy.
+///
+/// As the tokens are sent as bearer tokens just like access tokens, it implements `GetToken`,
+/// ignoring all scopes. Tokens are cached until they are about to expire.
+pub struct IdTokenAuthenticator {
+    source: IdTokenSource,
+    audience: String,
+    cache: TokenCache,
+}
+
+impl IdTokenAuthenticator {
+    fn new<S: Into<String>>(source: IdTokenSource, audience: S) -> Self {
+        IdTokenAuthenticator {
+            source,
+            audience: audience.into(),
+            cache: Default::default(),
+        }
+    }
+
+    /// Exchange JWTs signed with the given service account key for ID tokens.
+    pub fn from_service_account_key<S: Into<String>>(
+        key: &oauth2::ServiceAccountKey,
+        audience: S,
+    ) -> io::Result<Self> {
+        Ok(Self::new(
+            IdTokenSource::ServiceAccount {
+                client: https_client(),
+                signer: JwtSigner::new(key)?,
+                email: key.client_email.clone(),
+                token_uri: key.token_uri.clone(),
+            },
+            audience,
+        ))
+    }
+
+    /// Obtain ID tokens of a service account attached to the machine from its metadata server.
+    pub fn from_metadata_server<S: Into<String>>(
+        metadata: MetadataServerAuthenticator,
+        audience: S,
+    ) -> Self {
+        Self::new(IdTokenSource::Metadata(metadata), audience)
+    }
+
+    /// Obtain ID tokens of the impersonated service account, using IAM credentials'
+    /// `generateIdToken` method.
+    pub fn from_impersonation<S: Into<String>>(
+        impersonated: ImpersonatedAuthenticator,
+        audience: S,
+    ) -> Self {
+        Self::new(IdTokenSource::Impersonated(impersonated), audience)
+    }
+
+    async fn fetch(&self) -> std::result::Result<String, oauth2::Error> {
+        match self.source {
+            IdTokenSource::ServiceAccount {
+                ref client,
+                ref signer,
+                ref email,
+                ref token_uri,
+            } => {
+                let iat = Utc::now().timestamp();
+                let assertion = signer.sign(&json::json!({
+                    "iss": email,
+                    "sub": email,
+                    "aud": token_uri,
+                    "iat": iat,
+                    "exp": iat + 3600,
+                    "target_audience": self.audience,
+                }))?;
+                let body = url::form_urlencoded::Serializer::new(String::new())
+                    .append_pair("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer")
+                    .append_pair("assertion", &assertion)
+                    .finish();
+                let req = hyper::Request::post(token_uri)
+                    .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                    .body(hyper::body::Body::from(body))
+                    .unwrap();
+                fetch_token_response::<TokenResponse>(client, req)
+                    .await?
+                    .id_token
+                    .ok_or_else(|| {
+                        oauth2::Error::UserError(
+                            "Token response did not contain an id_token".to_string(),
+                        )
+                    })
+            }
+            IdTokenSource::Metadata(ref metadata) => {
+                let path = url::form_urlencoded::Serializer::new("identity?".to_string())
+                    .append_pair("audience", &self.audience)
+                    .append_pair("format", "full")
+                    .finish();
+                let body = metadata.get_body(&path).await?;
+                Ok(String::from_utf8_lossy(&body).trim().to_string())
+            }
+            IdTokenSource::Impersonated(ref impersonated) => {
+                #[derive(Deserialize)]
+                struct GenerateIdTokenResponse {
+                    token: String,
+                }
+                let res: GenerateIdTokenResponse = impersonated
+                    .call(
+                        "generateIdToken",
+                        json::json!({
+                            "audience": self.audience,
+                            "includeEmail": true,
+                        }),
+                    )
+                    .await?;
+                Ok(res.token)
+            }
+        }
+    }
+}
+
+impl GetToken for IdTokenAuthenticator {
+    fn token<'a>(&'a self, _scopes: &'a [&'a str]) -> TokenFuture<'a> {
+        Box::pin(async move {
+            if let Some(token) = self.cache.get(&[]) {
+                return Ok(token);
+            }
+            let token = id_token_to_access_token(self.fetch().await?);
+            self.cache.set(&[], token.clone());
+            Ok(token)
+        })
+    }
+}
+
+/// The issuers of ID tokens signed by Google.
+pub const GOOGLE_ID_TOKEN_ISSUERS: &[&str] =
+    &["https://accounts.google.com", "accounts.google.com"];
+
+/// A JSON Web Key Set, like the one at `https://www.googleapis.com/oauth2/v3/certs`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+/// A single RSA key of a `Jwks`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Jwk {
+    pub kid: Option<String>,
+    pub kty: String,
+    pub alg: Option<String>,
+    /// The modulus, URL-safe base64 encoded
+    pub n: String,
+    /// The exponent, URL-safe base64 encoded
+    pub e: String,
+}
+
+/// The claims of a verified ID token.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    /// All other claims
+    #[serde(flatten)]
+    pub other: json::Map<String, json::Value>,
+}
+
+#[derive(Debug)]
+pub enum IdTokenError {
+    /// The token is not a JSON Web Token, or its claims are missing or of the wrong type.
+    Malformed(String),
+    /// The token is signed with an algorithm other than RS256.
+    UnsupportedAlgorithm(String),
+    /// The token was signed by a key with the given id, which isn't part of the key set.
+    UnknownKey(Option<String>),
+    /// The signature doesn't match the token.
+    InvalidSignature,
+    /// The token expired at the given unix timestamp.
+    Expired(i64),
+    /// The token was issued by someone we don't trust.
+    InvalidIssuer(String),
+    /// The token is meant for someone else.
+    InvalidAudience(String),
+}
+
+impl Display for IdTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IdTokenError::Malformed(ref reason) => writeln!(f, "Malformed ID token: {}", reason),
+            IdTokenError::UnsupportedAlgorithm(ref alg) => {
+                writeln!(f, "ID token algorithm '{}' is not supported", alg)
+            }
+            IdTokenError::UnknownKey(ref kid) => writeln!(
+                f,
+                "ID token was signed by unknown key '{}'",
+                kid.as_deref().unwrap_or("")
+            ),
+            IdTokenError::InvalidSignature => writeln!(f, "ID token signature is invalid"),
+            IdTokenError::Expired(exp) => writeln!(f, "ID token expired at {}", exp),
+            IdTokenError::InvalidIssuer(ref iss) => {
+                writeln!(f, "ID token issuer '{}' is not trusted", iss)
+            }
+            IdTokenError::InvalidAudience(ref aud) => {
+                writeln!(f, "ID token audience '{}' is not the expected one", aud)
+            }
+        }
+    }
+}
+
+impl error::Error for IdTokenError {}
+
+/// Verifies ID tokens offline, against a given key set.
+///
+/// It checks the signature, the expiry, the issuer and the audience of each token.
+pub struct IdTokenVerifier {
+    keys: Vec<Jwk>,
+    audience: String,
+    issuers: Vec<String>,
+}
+
+impl IdTokenVerifier {
+    /// Accept tokens for the given audience, signed by Google with one of the given keys.
+    pub fn new<S: Into<String>>(jwks: Jwks, audience: S) -> Self {
+        IdTokenVerifier {
+            keys: jwks.keys.into_iter().filter(|k| k.kty == "RSA").collect(),
+            audience: audience.into(),
+            issuers: GOOGLE_ID_TOKEN_ISSUERS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+
+    /// Accept tokens of the given issuers instead of Google's.
+    pub fn issuers<I, S>(mut self, issuers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.issuers = issuers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Return the claims of the given token if it is valid.
+    pub fn verify(&self, token: &str) -> std::result::Result<IdTokenClaims, IdTokenError> {
+        let malformed = |reason: &str| IdTokenError::Malformed(reason.to_string());
+        let decode = |part: &str| {
+            base64::decode_config(part, base64::URL_SAFE_NO_PAD)
+                .map_err(|_| malformed("invalid base64"))
+        };
+
+        let mut parts = token.rsplitn(2, '.');
+        let (signature, message) = match (parts.next(), parts.next()) {
+            (Some(signature), Some(message)) => (decode(signature)?, message),
+            _ => return Err(malformed("expected three parts")),
+        };
+        let (header, claims) = match message.split_once('.') {
+            Some((header, claims)) => (decode(header)?, decode(claims)?),
+            None => return Err(malformed("expected three parts")),
+        };
+
+        #[derive(Deserialize)]
+        struct Header {
+            alg: String,
+            kid: Option<String>,
+        }
+        let header: Header =
+            json::from_slice(&header).map_err(|err| malformed(&err.to_string()))?;
+        if header.alg != "RS256" {
+            return Err(IdTokenError::UnsupportedAlgorithm(header.alg));
+        }
+        let key = self
+            .keys
+            .iter()
+            .find(|k| header.kid.is_none() || k.kid == header.kid)
+            .ok_or_else(|| IdTokenError::UnknownKey(header.kid.clone()))?;
+        ring::signature::RsaPublicKeyComponents {
+            n: decode(&key.n)?,
+            e: decode(&key.e)?,
+        }
+        .verify(
+            &ring::signature::RSA_PKCS1_2048_8192_SHA256,
+            message.as_bytes(),
+            &signature,
+        )
+        .map_err(|_| IdTokenError::InvalidSignature)?;
+
+        let claims: IdTokenClaims =
+            json::from_slice(&claims).map_err(|err| malformed(&err.to_string()))?;
+        if claims.exp <= Utc::now().timestamp() {
+            return Err(IdTokenError::Expired(claims.exp));
+        }
+        if !self.issuers.contains(&claims.iss) {
+            return Err(IdTokenError::InvalidIssuer(claims.iss));
+        }
+        if claims.aud != self.audience {
+            return Err(IdTokenError::InvalidAudience(claims.aud));
+        }
+        Ok(claims)
+    }
+}
+
+/// The environment variable pointing to the credentials file to use for Application Default
+/// Credentials.
+pub const CREDENTIALS_ENV: &str = "GOOGLE_APPLICATION_CREDENTIALS";
+
+/// Create a token source from the given credentials file, which may contain a service account
+/// key or the credentials of an authorized user.
+pub async fn credentials_from_file<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn GetToken>> {
+    #[derive(Deserialize)]
+    struct CredentialsType {
+        #[serde(rename = "type")]
+        kind: String,
+    }
+
+    let data = fs::read(path)?;
+    match json::from_slice::<CredentialsType>(&data)?.kind.as_str() {
+        "service_account" => Ok(Box::new(
+            service_account_authenticator(json::from_slice(&data)?, None).await?,
+        )),
+        "authorized_user" => Ok(Box::new(AuthorizedUserAuthenticator::new(
+            json::from_slice(&data)?,
+        ))),
+        kind => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported credentials type '{}'", kind),
+        )),
+    }
+}
+
+/// Returns the path of the credentials file written by `gcloud auth application-default login`.
+pub fn well_known_credentials_file() -> Option<PathBuf> {
+    let config_dir = match env::var_os("CLOUDSDK_CONFIG") {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => PathBuf::from(env::var_os("APPDATA")?).join("gcloud"),
+        None => PathBuf::from(env::var_os("HOME")?)
+            .join(".config")
+            .join("gcloud"),
+    };
+    Some(config_dir.join("application_default_credentials.json"))
+}
+
+/// Find credentials the way all Google Cloud client libraries do, and return a token source
+/// for them.
+///
+/// The following places are tried in order:
+///
+/// * the file named by the `GOOGLE_APPLICATION_CREDENTIALS` environment variable
+/// * the file written by `gcloud auth application-default login`
+/// * the metadata server
+pub async fn application_default_credentials() -> io::Result<Box<dyn GetToken>> {
+    if let Some(path) = env::var_os(CREDENTIALS_ENV) {
+        return credentials_from_file(path).await;
+    }
+    if let Some(path) = well_known_credentials_file().filter(|p| p.is_file()) {
+        return credentials_from_file(path).await;
+    }
+    Ok(Box::new(MetadataServerAuthenticator::new()))
+}
@@ -0,0 +1,19 @@
+//! Running the futures of the API to completion, for code which isn't async itself.
+use std::future::Future;
+use std::sync::OnceLock;
+
+/// Run `future` to completion on an internal current-thread runtime, blocking the current thread until then.
+///
+/// All calls share the same runtime, which keeps connections of the hyper client usable from one call to the next.
+/// It must not be called from within an async context, as nesting runtimes panics.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME
+        .get_or_init(|| {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("runtime to be created")
+        })
+        .block_on(future)
+}
@@ -0,0 +1,92 @@
+//! Caching responses by their `ETag`, to make polling unchanged resources cheap.
+//!
+//! If a hub has a [`ResponseCache`], the response of each `GET` call carrying an `ETag` is stored under
+//! a key made of its url and its scopes. When the same call is made again, its `If-None-Match` header
+//! asks the server to only send the resource if it changed, and the cached value is returned if it
+//! didn't.
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// A response body, along with the `ETag` it was sent with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedResponse {
+    pub etag: String,
+    /// The json encoded body of the response.
+    pub body: String,
+}
+
+/// A store for responses, as used by the hubs.
+pub trait ResponseCache: Send + Sync {
+    /// Return the response stored under `key`, if there is one.
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    /// Store the response under `key`, replacing the one stored before.
+    fn put(&self, key: String, response: CachedResponse);
+}
+
+/// The key under which the response to a call of `url` with the given scopes is cached.
+pub fn cache_key<'a, I>(url: &str, scopes: I) -> String
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let mut key = url.to_string();
+    for scope in scopes {
+        key.push(' ');
+        key.push_str(scope);
+    }
+    key
+}
+
+/// A cache in memory holding up to a given amount of responses, dropping the oldest ones first.
+pub struct MemoryCache {
+    capacity: usize,
+    entries: Mutex<(HashMap<String, CachedResponse>, VecDeque<String>)>,
+}
+
+impl MemoryCache {
+    /// A cache holding up to `capacity` responses.
+    pub fn new(capacity: usize) -> MemoryCache {
+        MemoryCache {
+            capacity,
+            entries: Mutex::new(Default::default()),
+        }
+    }
+
+    /// The amount of responses held.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop all responses.
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.0.clear();
+        entries.1.clear();
+    }
+}
+
+impl ResponseCache for MemoryCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        self.entries.lock().unwrap().0.get(key).cloned()
+    }
+
+    fn put(&self, key: String, response: CachedResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let (ref mut responses, ref mut order) = *entries;
+        if responses.insert(key.clone(), response).is_none() {
+            order.push_back(key);
+            while order.len() > self.capacity {
+                if let Some(oldest) = order.pop_front() {
+                    responses.remove(&oldest);
+                }
+            }
+        }
+    }
+}
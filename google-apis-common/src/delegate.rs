@@ -0,0 +1,398 @@
+//! The asynchronous counterpart of the `Delegate`, and delegates composed of others.
+//!
+//! All calls use an [`AsyncDelegate`], whose hooks may await, for instance to persist a resumable
+//! upload URL in a database or to refresh credentials, without blocking the executor. Every
+//! `Delegate` is an `AsyncDelegate` as well, and delegates can be combined with
+//! [`and()`](AsyncDelegate::and), like `RetryDelegate::default().and(logger)`.
+use std::future::{ready, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::lock::Mutex;
+use serde_json as json;
+
+use crate::{ContentRange, Delegate, JsonServerError, MethodInfo, Retry, ServerError};
+
+/// The future returned by the hooks of an `AsyncDelegate`.
+pub type DelegateFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// A delegate which call builders share with their clones, as set with `shared_delegate(...)`.
+///
+/// It is locked for the duration of each call, which makes concurrent calls sharing it wait for each other.
+pub type SharedDelegate = Arc<Mutex<dyn AsyncDelegate>>;
+
+/// Wrap `delegate`, so call builders and their clones can share it.
+pub fn shared_delegate<D: AsyncDelegate + 'static>(delegate: D) -> SharedDelegate {
+    Arc::new(Mutex::new(delegate))
+}
+
+/// A `Delegate` whose hooks may await.
+///
+/// Each method corresponds to the method of the same name of the `Delegate`, which documents when
+/// it is called. Only `chunk_size()` and `download_ranges()` are synchronous, as they merely
+/// configure a transfer.
+pub trait AsyncDelegate {
+    fn begin<'a>(&'a mut self, info: MethodInfo) -> DelegateFuture<'a, ()> {
+        let _ = info;
+        Box::pin(ready(()))
+    }
+
+    fn http_error<'a>(&'a mut self, err: &'a hyper::Error) -> DelegateFuture<'a, Retry> {
+        let _ = err;
+        Box::pin(ready(Retry::Abort))
+    }
+
+    fn api_key<'a>(&'a mut self) -> DelegateFuture<'a, Option<String>> {
+        Box::pin(ready(None))
+    }
+
+    fn token<'a>(
+        &'a mut self,
+        err: &'a oauth2::Error,
+    ) -> DelegateFuture<'a, Option<oauth2::AccessToken>> {
+        let _ = err;
+        Box::pin(ready(None))
+    }
+
+    fn upload_url<'a>(&'a mut self) -> DelegateFuture<'a, Option<String>> {
+        Box::pin(ready(None))
+    }
+
+    fn store_upload_url<'a>(&'a mut self, url: Option<&'a str>) -> DelegateFuture<'a, ()> {
+        let _ = url;
+        Box::pin(ready(()))
+    }
+
+    fn response_json_decode_error<'a>(
+        &'a mut self,
+        json_encoded_value: &'a str,
+        json_decode_error: &'a json::Error,
+    ) -> DelegateFuture<'a, ()> {
+        let _ = json_encoded_value;
+        let _ = json_decode_error;
+        Box::pin(ready(()))
+    }
+
+    fn http_failure<'a>(
+        &'a mut self,
+        response: &'a hyper::Response<hyper::body::Body>,
+        json_err: Option<JsonServerError>,
+        server_err: Option<ServerError>,
+    ) -> DelegateFuture<'a, Retry> {
+        let _ = (response, json_err, server_err);
+        Box::pin(ready(Retry::Abort))
+    }
+
+    fn pre_request<'a>(&'a mut self) -> DelegateFuture<'a, ()> {
+        Box::pin(ready(()))
+    }
+
+    fn chunk_size(&mut self) -> u64 {
+        1 << 23
+    }
+
+    fn cancel_chunk_upload<'a>(&'a mut self, chunk: &'a ContentRange) -> DelegateFuture<'a, bool> {
+        let _ = chunk;
+        Box::pin(ready(false))
+    }
+
+    fn cancel_chunk_download<'a>(
+        &'a mut self,
+        chunk: &'a ContentRange,
+    ) -> DelegateFuture<'a, bool> {
+        let _ = chunk;
+        Box::pin(ready(false))
+    }
+
+    fn download_ranges(&mut self) -> usize {
+        1
+    }
+
+    fn finished<'a>(&'a mut self, is_success: bool) -> DelegateFuture<'a, ()> {
+        let _ = is_success;
+        Box::pin(ready(()))
+    }
+
+    /// Combine this delegate with `other`, which is consulted after this one.
+    ///
+    /// Both delegates are informed about everything happening during a call. Whenever an answer is
+    /// needed, the first one given wins: a `Retry::After(...)` over `Retry::Abort`, and any value
+    /// over `None`. A chunk is cancelled if either delegate cancels it, and `chunk_size()` as well
+    /// as `download_ranges()` are taken from this delegate.
+    fn and<D: AsyncDelegate>(self, other: D) -> Chained<Self, D>
+    where
+        Self: Sized,
+    {
+        Chained(self, other)
+    }
+}
+
+impl<D: Delegate + ?Sized> AsyncDelegate for D {
+    fn begin<'a>(&'a mut self, info: MethodInfo) -> DelegateFuture<'a, ()> {
+        Delegate::begin(self, info);
+        Box::pin(ready(()))
+    }
+
+    fn http_error<'a>(&'a mut self, err: &'a hyper::Error) -> DelegateFuture<'a, Retry> {
+        Box::pin(ready(Delegate::http_error(self, err)))
+    }
+
+    fn api_key<'a>(&'a mut self) -> DelegateFuture<'a, Option<String>> {
+        Box::pin(ready(Delegate::api_key(self)))
+    }
+
+    fn token<'a>(
+        &'a mut self,
+        err: &'a oauth2::Error,
+    ) -> DelegateFuture<'a, Option<oauth2::AccessToken>> {
+        Box::pin(ready(Delegate::token(self, err)))
+    }
+
+    fn upload_url<'a>(&'a mut self) -> DelegateFuture<'a, Option<String>> {
+        Box::pin(ready(Delegate::upload_url(self)))
+    }
+
+    fn store_upload_url<'a>(&'a mut self, url: Option<&'a str>) -> DelegateFuture<'a, ()> {
+        Delegate::store_upload_url(self, url);
+        Box::pin(ready(()))
+    }
+
+    fn response_json_decode_error<'a>(
+        &'a mut self,
+        json_encoded_value: &'a str,
+        json_decode_error: &'a json::Error,
+    ) -> DelegateFuture<'a, ()> {
+        Delegate::response_json_decode_error(self, json_encoded_value, json_decode_error);
+        Box::pin(ready(()))
+    }
+
+    fn http_failure<'a>(
+        &'a mut self,
+        response: &'a hyper::Response<hyper::body::Body>,
+        json_err: Option<JsonServerError>,
+        server_err: Option<ServerError>,
+    ) -> DelegateFuture<'a, Retry> {
+        Box::pin(ready(Delegate::http_failure(
+            self, response, json_err, server_err,
+        )))
+    }
+
+    fn pre_request<'a>(&'a mut self) -> DelegateFuture<'a, ()> {
+        Delegate::pre_request(self);
+        Box::pin(ready(()))
+    }
+
+    fn chunk_size(&mut self) -> u64 {
+        Delegate::chunk_size(self)
+    }
+
+    fn cancel_chunk_upload<'a>(&'a mut self, chunk: &'a ContentRange) -> DelegateFuture<'a, bool> {
+        Box::pin(ready(Delegate::cancel_chunk_upload(self, chunk)))
+    }
+
+    fn cancel_chunk_download<'a>(
+        &'a mut self,
+        chunk: &'a ContentRange,
+    ) -> DelegateFuture<'a, bool> {
+        Box::pin(ready(Delegate::cancel_chunk_download(self, chunk)))
+    }
+
+    fn download_ranges(&mut self) -> usize {
+        Delegate::download_ranges(self)
+    }
+
+    fn finished<'a>(&'a mut self, is_success: bool) -> DelegateFuture<'a, ()> {
+        Delegate::finished(self, is_success);
+        Box::pin(ready(()))
+    }
+}
+
+/// Two delegates acting as one, as created by [`AsyncDelegate::and()`].
+pub struct Chained<A, B>(pub A, pub B);
+
+impl<A: AsyncDelegate, B: AsyncDelegate> AsyncDelegate for Chained<A, B> {
+    fn begin<'a>(&'a mut self, info: MethodInfo) -> DelegateFuture<'a, ()> {
+        Box::pin(async move {
+            self.0.begin(info.clone()).await;
+            self.1.begin(info).await
+        })
+    }
+
+    fn http_error<'a>(&'a mut self, err: &'a hyper::Error) -> DelegateFuture<'a, Retry> {
+        Box::pin(async move {
+            match self.0.http_error(err).await {
+                Retry::Abort => self.1.http_error(err).await,
+                retry => retry,
+            }
+        })
+    }
+
+    fn api_key<'a>(&'a mut self) -> DelegateFuture<'a, Option<String>> {
+        Box::pin(async move {
+            match self.0.api_key().await {
+                None => self.1.api_key().await,
+                key => key,
+            }
+        })
+    }
+
+    fn token<'a>(
+        &'a mut self,
+        err: &'a oauth2::Error,
+    ) -> DelegateFuture<'a, Option<oauth2::AccessToken>> {
+        Box::pin(async move {
+            match self.0.token(err).await {
+                None => self.1.token(err).await,
+                token => token,
+            }
+        })
+    }
+
+    fn upload_url<'a>(&'a mut self) -> DelegateFuture<'a, Option<String>> {
+        Box::pin(async move {
+            match self.0.upload_url().await {
+                None => self.1.upload_url().await,
+                url => url,
+            }
+        })
+    }
+
+    fn store_upload_url<'a>(&'a mut self, url: Option<&'a str>) -> DelegateFuture<'a, ()> {
+        Box::pin(async move {
+            self.0.store_upload_url(url).await;
+            self.1.store_upload_url(url).await
+        })
+    }
+
+    fn response_json_decode_error<'a>(
+        &'a mut self,
+        json_encoded_value: &'a str,
+        json_decode_error: &'a json::Error,
+    ) -> DelegateFuture<'a, ()> {
+        Box::pin(async move {
+            self.0
+                .response_json_decode_error(json_encoded_value, json_decode_error)
+                .await;
+            self.1
+                .response_json_decode_error(json_encoded_value, json_decode_error)
+                .await
+        })
+    }
+
+    fn http_failure<'a>(
+        &'a mut self,
+        response: &'a hyper::Response<hyper::body::Body>,
+        json_err: Option<JsonServerError>,
+        server_err: Option<ServerError>,
+    ) -> DelegateFuture<'a, Retry> {
+        Box::pin(async move {
+            match self
+                .0
+                .http_failure(response, json_err.clone(), server_err.clone())
+                .await
+            {
+                Retry::Abort => self.1.http_failure(response, json_err, server_err).await,
+                retry => retry,
+            }
+        })
+    }
+
+    fn pre_request<'a>(&'a mut self) -> DelegateFuture<'a, ()> {
+        Box::pin(async move {
+            self.0.pre_request().await;
+            self.1.pre_request().await
+        })
+    }
+
+    fn chunk_size(&mut self) -> u64 {
+        self.0.chunk_size()
+    }
+
+    fn cancel_chunk_upload<'a>(&'a mut self, chunk: &'a ContentRange) -> DelegateFuture<'a, bool> {
+        Box::pin(async move {
+            self.0.cancel_chunk_upload(chunk).await || self.1.cancel_chunk_upload(chunk).await
+        })
+    }
+
+    fn cancel_chunk_download<'a>(
+        &'a mut self,
+        chunk: &'a ContentRange,
+    ) -> DelegateFuture<'a, bool> {
+        Box::pin(async move {
+            self.0.cancel_chunk_download(chunk).await || self.1.cancel_chunk_download(chunk).await
+        })
+    }
+
+    fn download_ranges(&mut self) -> usize {
+        self.0.download_ranges()
+    }
+
+    fn finished<'a>(&'a mut self, is_success: bool) -> DelegateFuture<'a, ()> {
+        Box::pin(async move {
+            self.0.finished(is_success).await;
+            self.1.finished(is_success).await
+        })
+    }
+}
+
+/// A delegate retrying failed requests with an exponential backoff.
+///
+/// It retries after errors of the connection, as well as after responses with status 429 or 5xx,
+/// up to `max_retries` times per call. The backoff is awaited, and doesn't block the executor meanwhile.
+pub struct RetryDelegate {
+    /// The amount of retries per call.
+    pub max_retries: u32,
+    /// The time to wait before the first retry, which doubles with each further one.
+    pub initial_backoff: Duration,
+    retries: u32,
+}
+
+impl RetryDelegate {
+    pub fn new(max_retries: u32, initial_backoff: Duration) -> RetryDelegate {
+        RetryDelegate {
+            max_retries,
+            initial_backoff,
+            retries: 0,
+        }
+    }
+
+    fn retry(&mut self) -> Retry {
+        if self.retries >= self.max_retries {
+            return Retry::Abort;
+        }
+        let backoff = self.initial_backoff * 2u32.saturating_pow(self.retries);
+        self.retries += 1;
+        Retry::After(backoff)
+    }
+}
+
+impl Default for RetryDelegate {
+    fn default() -> RetryDelegate {
+        RetryDelegate::new(3, Duration::from_millis(500))
+    }
+}
+
+impl Delegate for RetryDelegate {
+    fn begin(&mut self, _info: MethodInfo) {
+        self.retries = 0;
+    }
+
+    fn http_error(&mut self, _err: &hyper::Error) -> Retry {
+        self.retry()
+    }
+
+    fn http_failure(
+        &mut self,
+        response: &hyper::Response<hyper::body::Body>,
+        _err: Option<JsonServerError>,
+        _: Option<ServerError>,
+    ) -> Retry {
+        let status = response.status();
+        if status == hyper::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            self.retry()
+        } else {
+            Retry::Abort
+        }
+    }
+}
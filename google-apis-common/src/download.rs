@@ -0,0 +1,349 @@
+//! Downloading media in chunks with `Range` requests.
+//!
+//! The [`DownloadHelper`] resumes where it left off, be it after a dropped connection within one call,
+//! or with a partially written file from an earlier one. It can split a download into ranges which are
+//! fetched in parallel, and verifies the result against a [`Checksum`] provided by the API, like
+//! `Object.md5Hash` and `Object.crc32c` of *storage1* or `File.md5Checksum` of *drive3*.
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use futures_util::lock::Mutex;
+use hyper::header::{AUTHORIZATION, CONTENT_RANGE, RANGE, USER_AGENT};
+use hyper::StatusCode;
+use serde_json as json;
+
+use crate::{
+    AsyncDelegate, Chunk, ContentRange, Error, ErrorResponse, GetToken, HttpsClient, Result, Retry,
+};
+
+/// A checksum of media, to verify downloads with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Checksum {
+    /// The MD5 digest.
+    Md5([u8; 16]),
+    /// The CRC32C checksum, as defined by [RFC4960](https://tools.ietf.org/html/rfc4960#appendix-B).
+    Crc32c(u32),
+}
+
+impl Checksum {
+    /// Decode a base64 encoded MD5 digest, like `Object.md5Hash` of *storage1*.
+    pub fn md5_base64(value: &str) -> Option<Checksum> {
+        let bytes = base64::decode(value).ok()?;
+        let mut digest = [0u8; 16];
+        if bytes.len() != digest.len() {
+            return None;
+        }
+        digest.copy_from_slice(&bytes);
+        Some(Checksum::Md5(digest))
+    }
+
+    /// Decode a hex encoded MD5 digest, like `File.md5Checksum` of *drive3*.
+    pub fn md5_hex(value: &str) -> Option<Checksum> {
+        let mut digest = [0u8; 16];
+        if value.len() != digest.len() * 2 || !value.is_ascii() {
+            return None;
+        }
+        for (i, byte) in digest.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&value[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(Checksum::Md5(digest))
+    }
+
+    /// Decode a base64 encoded CRC32C checksum in big-endian byte order, like `Object.crc32c` of *storage1*.
+    pub fn crc32c_base64(value: &str) -> Option<Checksum> {
+        let bytes = base64::decode(value).ok()?;
+        let mut checksum = [0u8; 4];
+        if bytes.len() != checksum.len() {
+            return None;
+        }
+        checksum.copy_from_slice(&bytes);
+        Some(Checksum::Crc32c(u32::from_be_bytes(checksum)))
+    }
+
+    /// Compute a checksum of the same kind from everything `reader` yields.
+    pub fn compute<R: Read>(&self, reader: &mut R) -> std::io::Result<Checksum> {
+        let mut buf = vec![0u8; 1 << 16];
+        match *self {
+            Checksum::Md5(_) => {
+                let mut context = md5::Context::new();
+                loop {
+                    match reader.read(&mut buf)? {
+                        0 => return Ok(Checksum::Md5(context.compute().0)),
+                        n => context.consume(&buf[..n]),
+                    }
+                }
+            }
+            Checksum::Crc32c(_) => {
+                let mut crc = 0;
+                loop {
+                    match reader.read(&mut buf)? {
+                        0 => return Ok(Checksum::Crc32c(crc)),
+                        n => crc = crc32c::crc32c_append(crc, &buf[..n]),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Checksum::Md5(ref digest) => write!(f, "md5:{}", base64::encode(digest)),
+            Checksum::Crc32c(crc) => write!(f, "crc32c:{}", base64::encode(crc.to_be_bytes())),
+        }
+    }
+}
+
+/// A utility type to download media from start to end, in chunks of the delegate's `chunk_size()`.
+///
+/// Whenever the delegate decides to retry after an error, the download continues with the chunk
+/// that failed. The delegate is informed about each chunk through `cancel_chunk_download()`, and
+/// determines the amount of ranges downloaded in parallel with `download_ranges()`.
+///
+/// The token is obtained from `auth` for each chunk, which refreshes it once it expired, so that long
+/// downloads don't fail with *401 Unauthorized* halfway through.
+pub struct DownloadHelper<'a> {
+    pub client: &'a HttpsClient,
+    pub delegate: &'a mut dyn AsyncDelegate,
+    pub user_agent: &'a str,
+    /// The token source to authorize each request with, or `None` to send no token.
+    pub auth: Option<&'a dyn GetToken>,
+    /// The scopes of the token.
+    pub scopes: &'a [&'a str],
+    /// The url of the media, usually with `alt=media` as part of its query.
+    pub url: &'a str,
+    /// If set, the downloaded media must match it.
+    pub checksum: Option<Checksum>,
+}
+
+impl<'a> DownloadHelper<'a> {
+    /// Download the media into `writer`, and return its size in bytes.
+    ///
+    /// Whatever `writer` holds already is taken as the beginning of the media, which makes it possible to resume
+    /// a download into a file which was interrupted. Such a download isn't split into parallel ranges.
+    pub async fn download<W>(self, writer: &mut W) -> Result<u64>
+    where
+        W: Read + Write + Seek,
+    {
+        let start = writer.seek(SeekFrom::End(0)).map_err(Error::Io)?;
+        let chunk_size = self.delegate.chunk_size().max(1);
+        let ranges = self.delegate.download_ranges().max(1) as u64;
+        let checksum = self.checksum.clone();
+        let download = Download {
+            client: self.client,
+            user_agent: self.user_agent,
+            auth: self.auth,
+            scopes: self.scopes,
+            url: self.url,
+            chunk_size,
+            delegate: Mutex::new(self.delegate),
+            writer: RefCell::new(writer),
+            total_length: Cell::new(None),
+        };
+
+        let size = if start == 0 && ranges > 1 {
+            // the first chunk tells the size of the media, which is then split into ranges
+            let first = download.fetch(0, Some(chunk_size - 1)).await?;
+            match download.total_length.get() {
+                Some(total) => {
+                    let rest = total.saturating_sub(first);
+                    let range_size = rest.div_ceil(ranges);
+                    let mut parts = Vec::new();
+                    let mut from = first;
+                    while from < total {
+                        let last = (from + range_size).min(total) - 1;
+                        parts.push(download.fetch(from, Some(last)));
+                        from = last + 1;
+                    }
+                    futures_util::future::try_join_all(parts).await?;
+                    total
+                }
+                // without a size, the rest can't be split, and is downloaded up to its end
+                None => download.fetch(first, None).await?,
+            }
+        } else {
+            download.fetch(start, None).await?
+        };
+
+        let writer = download.writer.into_inner();
+        writer.flush().map_err(Error::Io)?;
+        if let Some(expected) = checksum {
+            writer.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
+            let actual = expected
+                .compute(&mut writer.take(size))
+                .map_err(Error::Io)?;
+            if actual != expected {
+                return Err(Error::ChecksumMismatch(expected, actual));
+            }
+        }
+        Ok(size)
+    }
+}
+
+/// The state shared by all ranges of a download.
+struct Download<'a, 'w, W> {
+    client: &'a HttpsClient,
+    user_agent: &'a str,
+    auth: Option<&'a dyn GetToken>,
+    scopes: &'a [&'a str],
+    url: &'a str,
+    chunk_size: u64,
+    delegate: Mutex<&'a mut dyn AsyncDelegate>,
+    writer: RefCell<&'w mut W>,
+    total_length: Cell<Option<u64>>,
+}
+
+impl<'a, 'w, W: Write + Seek> Download<'a, 'w, W> {
+    /// Download the bytes from `first` up to and including `last`, or up to the end of the media,
+    /// and return the position after the last byte written.
+    async fn fetch(&self, mut first: u64, last: Option<u64>) -> Result<u64> {
+        loop {
+            let end = match (last, self.total_length.get()) {
+                (Some(last), Some(total)) => Some(last.min(total.saturating_sub(1))),
+                (last, total) => last.or_else(|| total.map(|t| t.saturating_sub(1))),
+            };
+            if end.map(|end| first > end).unwrap_or(false) || self.total_length.get() == Some(first)
+            {
+                return Ok(first);
+            }
+            let chunk = Chunk {
+                first,
+                last: (first + self.chunk_size - 1).min(end.unwrap_or(u64::MAX)),
+            };
+            let range = ContentRange {
+                range: Some(chunk.clone()),
+                total_length: self.total_length.get().unwrap_or(0),
+            };
+            if self
+                .delegate
+                .lock()
+                .await
+                .cancel_chunk_download(&range)
+                .await
+            {
+                return Err(Error::Cancelled);
+            }
+
+            let mut req_builder = hyper::Request::builder()
+                .method(hyper::Method::GET)
+                .uri(self.url)
+                .header(USER_AGENT, self.user_agent.to_string())
+                .header(RANGE, format!("bytes={}", chunk));
+            if let Some(auth) = self.auth {
+                let token = match auth.token(self.scopes).await {
+                    Ok(token) => token,
+                    Err(err) => match self.delegate.lock().await.token(&err).await {
+                        Some(token) => token,
+                        None => return Err(Error::MissingToken(err)),
+                    },
+                };
+                req_builder =
+                    req_builder.header(AUTHORIZATION, format!("Bearer {}", token.as_str()));
+            }
+            let res = match self
+                .client
+                .request(req_builder.body(hyper::body::Body::empty()).unwrap())
+                .await
+            {
+                Ok(res) => res,
+                Err(err) => {
+                    if let Retry::After(d) = self.delegate.lock().await.http_error(&err).await {
+                        tokio::time::sleep(d).await;
+                        continue;
+                    }
+                    return Err(Error::HttpError(err));
+                }
+            };
+
+            let (res_parts, res_body) = res.into_parts();
+            let res_body_bytes = match hyper::body::to_bytes(res_body).await {
+                Ok(bytes) => bytes,
+                // the connection dropped within the chunk, which is requested again
+                Err(err) => {
+                    if let Retry::After(d) = self.delegate.lock().await.http_error(&err).await {
+                        tokio::time::sleep(d).await;
+                        continue;
+                    }
+                    return Err(Error::HttpError(err));
+                }
+            };
+
+            match res_parts.status {
+                StatusCode::PARTIAL_CONTENT => {
+                    let content_range = res_parts
+                        .headers
+                        .get(CONTENT_RANGE)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(parse_content_range);
+                    match content_range {
+                        Some((range_first, total)) if range_first == first => {
+                            self.total_length
+                                .set(total.or_else(|| self.total_length.get()));
+                        }
+                        _ => {
+                            return Err(Error::Failure(hyper::Response::from_parts(
+                                res_parts,
+                                res_body_bytes.into(),
+                            )))
+                        }
+                    }
+                    self.write_at(first, &res_body_bytes).map_err(Error::Io)?;
+                    first += res_body_bytes.len() as u64;
+                    if res_body_bytes.is_empty() {
+                        return Ok(first);
+                    }
+                }
+                // the server ignored the range, and sent all of the media
+                StatusCode::OK => {
+                    self.write_at(0, &res_body_bytes).map_err(Error::Io)?;
+                    let total = res_body_bytes.len() as u64;
+                    self.total_length.set(Some(total));
+                    return Ok(total);
+                }
+                // the media is empty, or ends right where we are
+                StatusCode::RANGE_NOT_SATISFIABLE if self.total_length.get().is_none() => {
+                    self.total_length.set(Some(first));
+                    return Ok(first);
+                }
+                _ => {
+                    let res_body_string = String::from_utf8_lossy(&res_body_bytes).into_owned();
+                    let reconstructed_result =
+                        hyper::Response::from_parts(res_parts, res_body_string.clone().into());
+                    if let Retry::After(d) = self
+                        .delegate
+                        .lock()
+                        .await
+                        .http_failure(
+                            &reconstructed_result,
+                            json::from_str(&res_body_string).ok(),
+                            json::from_str(&res_body_string).ok(),
+                        )
+                        .await
+                    {
+                        tokio::time::sleep(d).await;
+                        continue;
+                    }
+                    return match json::from_str::<ErrorResponse>(&res_body_string) {
+                        Err(_) => Err(Error::Failure(reconstructed_result)),
+                        Ok(serr) => Err(Error::BadRequest(serr)),
+                    };
+                }
+            }
+        }
+    }
+
+    fn write_at(&self, position: u64, bytes: &[u8]) -> std::io::Result<()> {
+        let mut writer = self.writer.borrow_mut();
+        writer.seek(SeekFrom::Start(position))?;
+        writer.write_all(bytes)
+    }
+}
+
+/// Parse a `Content-Range` header value like `bytes 0-99/1234` into the first byte and the total length, if known.
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let first = range.split_once('-')?.0.parse().ok()?;
+    Some((first, total.parse().ok()))
+}
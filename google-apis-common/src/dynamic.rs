@@ -0,0 +1,917 @@
+//! Calling the methods of any API through its discovery document, without a generated crate.
+//!
+//! A [`DynamicHub`] resolves methods by their name, like `objects.insert`, checks and expands their parameters,
+//! and sends request values as `serde_json::Value`. Just like the generated hubs, it uses a [`Delegate`] for
+//! retries and progress, any [`GetToken`] implementation for authorization, and supports uploads.
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::mem;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+
+use hyper::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, LOCATION, USER_AGENT};
+use mime::Mime;
+use serde_json as json;
+
+use crate::{
+    check_upload, expand_uri_template, AsyncDelegate, DefaultDelegate, Error, ErrorResponse,
+    GetToken, HttpsClient, JsonServerError, MethodInfo, MultiPartReader, RateLimiter, ReadSeek,
+    ResponseMeta, Result, ResumableUploadHelper, Retry, ServerError, SharedDelegate,
+    ValidationError,
+};
+
+/// The url of the discovery document of the given API, as served by the discovery API.
+pub fn discovery_document_url(name: &str, version: &str) -> String {
+    format!(
+        "https://www.googleapis.com/discovery/v1/apis/{}/{}/rest",
+        name, version
+    )
+}
+
+/// A discovery document, describing the resources, methods and parameters of an API.
+///
+/// Only the parts needed to call methods are decoded, schemas are not.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveryDocument {
+    pub name: String,
+    pub version: String,
+    pub root_url: String,
+    #[serde(default)]
+    pub service_path: String,
+    /// Parameters which apply to all methods, like `fields` or `quotaUser`.
+    #[serde(default)]
+    pub parameters: HashMap<String, ParameterDescription>,
+    #[serde(default)]
+    pub resources: HashMap<String, ResourceDescription>,
+    /// Methods which don't belong to a resource.
+    #[serde(default)]
+    pub methods: HashMap<String, MethodDescription>,
+}
+
+/// A resource of an API, with its methods and nested resources.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ResourceDescription {
+    #[serde(default)]
+    pub methods: HashMap<String, MethodDescription>,
+    #[serde(default)]
+    pub resources: HashMap<String, ResourceDescription>,
+}
+
+/// A method of an API, as described by its discovery document.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodDescription {
+    /// The fully qualified id, like `storage.objects.insert`.
+    pub id: String,
+    /// The path relative to the base url, as URI template.
+    pub path: String,
+    pub http_method: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, ParameterDescription>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    pub request: Option<SchemaReference>,
+    pub response: Option<SchemaReference>,
+    #[serde(default)]
+    pub supports_media_upload: bool,
+    pub media_upload: Option<MediaUploadDescription>,
+}
+
+/// Names the schema of a request or response.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SchemaReference {
+    #[serde(rename = "$ref")]
+    pub schema: String,
+}
+
+/// The constraints of media uploads, and the paths of the upload protocols.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaUploadDescription {
+    #[serde(default)]
+    pub accept: Vec<String>,
+    /// The maximum size of the media, like `5TB`.
+    pub max_size: Option<String>,
+    /// The protocols, usually `simple` and `resumable`, by name.
+    #[serde(default)]
+    pub protocols: HashMap<String, UploadProtocolDescription>,
+}
+
+/// Where media is uploaded to with a particular protocol.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct UploadProtocolDescription {
+    #[serde(default)]
+    pub multipart: bool,
+    /// The path relative to the root url, as URI template.
+    pub path: String,
+}
+
+/// A parameter of a method, or of all methods of an API.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ParameterDescription {
+    #[serde(rename = "type", default)]
+    pub type_: String,
+    /// Either `path` or `query`.
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub repeated: bool,
+    pub pattern: Option<String>,
+    #[serde(rename = "enum", default)]
+    pub enum_values: Vec<String>,
+    pub minimum: Option<String>,
+    pub maximum: Option<String>,
+}
+
+impl FromStr for DiscoveryDocument {
+    type Err = json::Error;
+
+    fn from_str(s: &str) -> std::result::Result<DiscoveryDocument, json::Error> {
+        json::from_str(s)
+    }
+}
+
+impl DiscoveryDocument {
+    /// Read a discovery document from a file, like one of the `*-api.json` files of google-apis-rs.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<DiscoveryDocument> {
+        let file = std::fs::File::open(path)?;
+        json::from_reader(io::BufReader::new(file))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Fetch the discovery document of the given API, like `storage` and `v1`, from the discovery API.
+    pub async fn fetch(
+        client: &HttpsClient,
+        name: &str,
+        version: &str,
+    ) -> Result<DiscoveryDocument> {
+        let url = discovery_document_url(name, version);
+        let res = client
+            .get(url.parse().expect("discovery url to be valid"))
+            .await
+            .map_err(Error::HttpError)?;
+        let (res_parts, res_body) = res.into_parts();
+        let res_body_bytes = hyper::body::to_bytes(res_body)
+            .await
+            .map_err(Error::HttpError)?;
+        let res_body_string = String::from_utf8_lossy(&res_body_bytes).into_owned();
+        if !res_parts.status.is_success() {
+            return Err(Error::Failure(hyper::Response::from_parts(
+                res_parts,
+                res_body_string.into(),
+            )));
+        }
+        json::from_str(&res_body_string).map_err(|err| Error::JsonDecodeError(res_body_string, err))
+    }
+
+    /// The url all method paths are relative to.
+    pub fn base_url(&self) -> String {
+        self.root_url.clone() + &self.service_path
+    }
+
+    /// Return the method with the given name, like `objects.insert` or `projects.topics.get`.
+    /// Its fully qualified id, like `storage.objects.insert`, works as well.
+    pub fn method(&self, name: &str) -> Option<&MethodDescription> {
+        fn find<'a>(
+            resources: &'a HashMap<String, ResourceDescription>,
+            methods: &'a HashMap<String, MethodDescription>,
+            path: &[&str],
+        ) -> Option<&'a MethodDescription> {
+            match path {
+                [] => None,
+                [method] => methods.get(*method),
+                [resource, rest @ ..] => resources
+                    .get(*resource)
+                    .and_then(|r| find(&r.resources, &r.methods, rest)),
+            }
+        }
+
+        let path: Vec<&str> = name.split('.').collect();
+        find(&self.resources, &self.methods, &path).or_else(|| match path.split_first() {
+            Some((api, rest)) if *api == self.name => find(&self.resources, &self.methods, rest),
+            _ => None,
+        })
+    }
+}
+
+impl MethodDescription {
+    /// Return the url of the scope with the least privileges which authorizes this method, if it needs one.
+    ///
+    /// Read-only scopes come first. Within read-only and other scopes, the ones of the service come before the
+    /// ones which encompass others, like *cloud-platform.read-only*, or *drive* in presence of *drive.file*.
+    pub fn minimal_scope(&self) -> Option<&str> {
+        // a scope is broad if its name is the prefix of the name of another scope of the same tier
+        let is_broad = |url: &str| {
+            let name = scope_name(url);
+            name.contains("full")
+                || name == "cloud-platform"
+                || self.scopes.iter().any(|other| {
+                    other != url
+                        && is_read_only_scope(other) == is_read_only_scope(url)
+                        && scope_name(other).starts_with(&format!("{}.", name))
+                })
+        };
+        self.scopes
+            .iter()
+            .min_by_key(|url| (!is_read_only_scope(url), is_broad(url), url.as_str()))
+            .map(String::as_str)
+    }
+}
+
+const READ_ONLY_SUFFIXES: [&str; 4] = ["readonly", "read_only", "read-only", "read.only"];
+
+fn is_read_only_scope(url: &str) -> bool {
+    READ_ONLY_SUFFIXES.iter().any(|ro| url.contains(ro))
+}
+
+/// The name of a scope without its read-only suffix, like `drive` for `https://www.googleapis.com/auth/drive.readonly`.
+fn scope_name(url: &str) -> &str {
+    let base = url.rsplit('/').next().unwrap_or(url);
+    READ_ONLY_SUFFIXES
+        .iter()
+        .find_map(|ro| base.strip_suffix(ro))
+        .map(|name| name.trim_end_matches(['.', '-', '_']))
+        .unwrap_or(base)
+}
+
+/// Return the `&'static str` the delegate expects as method id, keeping only one copy of each id.
+fn static_method_id(id: &str) -> &'static str {
+    static IDS: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut ids = IDS
+        .get_or_init(Default::default)
+        .lock()
+        .expect("method ids not to be poisoned");
+    match ids.get(id) {
+        Some(id) => id,
+        None => {
+            let id: &'static str = Box::leak(id.to_string().into_boxed_str());
+            ids.insert(id);
+            id
+        }
+    }
+}
+
+/// Parse a size like `5TB` or `100MB` into bytes.
+fn size_to_bytes(size: &str) -> Option<u64> {
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (value, unit) = size.split_at(split);
+    let factor: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "KB" => 1 << 10,
+        "MB" => 1 << 20,
+        "GB" => 1 << 30,
+        "TB" => 1 << 40,
+        _ => return None,
+    };
+    value.parse::<u64>().ok().map(|v| v * factor)
+}
+
+/// Central instance to call the methods of any API described by a [`DiscoveryDocument`].
+///
+/// ```ignore
+/// let document = client::DiscoveryDocument::from_path("etc/api/storage/v1/storage-api.json")?;
+/// let auth = client::application_default_credentials().await?;
+/// let hub = client::DynamicHub::new(client::https_client(), auth, document);
+/// let (_, buckets) = hub.method("buckets.list").unwrap()
+///                       .param("project", "my-project")
+///                       .doit().await?;
+/// ```
+pub struct DynamicHub {
+    client: HttpsClient,
+    auth: Box<dyn GetToken>,
+    document: DiscoveryDocument,
+    _user_agent: String,
+    _base_url: String,
+    _root_url: String,
+    _rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl DynamicHub {
+    /// Create a new hub for the API described by `document`, which obtains its tokens from the given authenticator.
+    pub fn new<A: 'static + GetToken>(
+        client: HttpsClient,
+        authenticator: A,
+        document: DiscoveryDocument,
+    ) -> DynamicHub {
+        let mut auth: Box<dyn GetToken> = Box::new(authenticator);
+        auth.set_root_url(&document.root_url);
+        DynamicHub {
+            client,
+            auth,
+            _user_agent: "google-api-rust-client/dynamic".to_string(),
+            _base_url: document.base_url(),
+            _root_url: document.root_url.clone(),
+            _rate_limiter: None,
+            document,
+        }
+    }
+
+    /// The discovery document of the API.
+    pub fn document(&self) -> &DiscoveryDocument {
+        &self.document
+    }
+
+    /// Return a builder for a call of the method with the given name, like `objects.insert`,
+    /// or None if the API doesn't have it.
+    pub fn method(&self, name: &str) -> Option<DynamicCall<'_>> {
+        self.document.method(name).map(|method| DynamicCall {
+            hub: self,
+            method,
+            params: Vec::new(),
+            request: None,
+            delegate: None,
+            shared_delegate: None,
+            scopes: BTreeSet::new(),
+        })
+    }
+
+    /// Set the user-agent header field to use in all requests to the server.
+    /// It defaults to `google-api-rust-client/dynamic`.
+    ///
+    /// Returns the previously set user-agent.
+    pub fn user_agent(&mut self, agent_name: String) -> String {
+        mem::replace(&mut self._user_agent, agent_name)
+    }
+
+    /// Set the base url to use in all requests to the server.
+    /// It defaults to the one of the discovery document.
+    ///
+    /// Returns the previously set base url.
+    pub fn base_url(&mut self, new_base_url: String) -> String {
+        mem::replace(&mut self._base_url, new_base_url)
+    }
+
+    /// Set the root url to use in all requests to the server, which is used by uploads.
+    /// It defaults to the one of the discovery document.
+    ///
+    /// Returns the previously set root url.
+    pub fn root_url(&mut self, new_root_url: String) -> String {
+        self.auth.set_root_url(&new_root_url);
+        mem::replace(&mut self._root_url, new_root_url)
+    }
+
+    /// Set a rate limiter, which paces all calls of this hub before they are sent.
+    ///
+    /// Returns the previously set rate limiter.
+    pub fn rate_limiter(
+        &mut self,
+        new_rate_limiter: Option<Arc<RateLimiter>>,
+    ) -> Option<Arc<RateLimiter>> {
+        mem::replace(&mut self._rate_limiter, new_rate_limiter)
+    }
+}
+
+/// A builder for a call of a method of a [`DynamicHub`].
+pub struct DynamicCall<'a> {
+    hub: &'a DynamicHub,
+    method: &'a MethodDescription,
+    params: Vec<(String, String)>,
+    request: Option<json::Value>,
+    delegate: Option<&'a mut dyn AsyncDelegate>,
+    shared_delegate: Option<SharedDelegate>,
+    scopes: BTreeSet<String>,
+}
+
+/// A clone has the same parameters and shares the delegate set with `shared_delegate()`, but not the one
+/// set with `delegate()`, which is borrowed by this call. Thus `doit_ref()` refuses to make calls without it.
+impl<'a> Clone for DynamicCall<'a> {
+    fn clone(&self) -> DynamicCall<'a> {
+        DynamicCall {
+            hub: self.hub,
+            method: self.method,
+            params: self.params.clone(),
+            request: self.request.clone(),
+            delegate: None,
+            shared_delegate: self.shared_delegate.clone(),
+            scopes: self.scopes.clone(),
+        }
+    }
+}
+
+impl<'a> DynamicCall<'a> {
+    /// The description of the method this call is for.
+    pub fn description(&self) -> &'a MethodDescription {
+        self.method
+    }
+
+    /// Set a parameter of the method, or one of the parameters all methods of the API have.
+    /// Repeated parameters are set by calling this once per value.
+    pub fn param<K, V>(mut self, name: K, value: V) -> DynamicCall<'a>
+    where
+        K: AsRef<str>,
+        V: ToString,
+    {
+        self.params
+            .push((name.as_ref().to_string(), value.to_string()));
+        self
+    }
+
+    /// Set the request value, which is sent as json.
+    pub fn request(mut self, value: json::Value) -> DynamicCall<'a> {
+        self.request = Some(value);
+        self
+    }
+
+    /// The delegate implementation is consulted whenever there is an intermediate result, or if something goes wrong
+    /// while executing the actual API request.
+    pub fn delegate(mut self, new_value: &'a mut dyn AsyncDelegate) -> DynamicCall<'a> {
+        self.delegate = Some(new_value);
+        self
+    }
+
+    /// Set a delegate which is shared with the clones of this call, and locked for the duration of each call.
+    /// A delegate set with `delegate()` takes precedence over it.
+    pub fn shared_delegate(mut self, new_value: SharedDelegate) -> DynamicCall<'a> {
+        self.shared_delegate = Some(new_value);
+        self
+    }
+
+    /// Identifies an authorization scope for the call by its url. Without any, the one with the least
+    /// privileges authorizing the method is used.
+    pub fn add_scope<T: AsRef<str>>(mut self, scope: T) -> DynamicCall<'a> {
+        self.scopes.insert(scope.as_ref().to_string());
+        self
+    }
+
+    /// Check the parameters against the description of the method, without sending anything.
+    ///
+    /// Unknown parameters, missing required ones, repeated values of parameters which aren't repeated,
+    /// values which aren't in their enumeration or out of their bounds, as well as a missing request value
+    /// are reported. Patterns are checked with the `validation` feature only.
+    /// This is done by `doit()` and the upload methods as well.
+    #[allow(clippy::result_large_err)]
+    pub fn validate(&self) -> Result<()> {
+        self.check(false)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn check(&self, is_media_upload: bool) -> Result<()> {
+        let mut errors = Vec::new();
+        let mut error = |field: &str, message: String| {
+            errors.push(ValidationError {
+                field: field.to_string(),
+                message,
+            })
+        };
+
+        for (name, value) in self.params.iter() {
+            let param = match self
+                .method
+                .parameters
+                .get(name)
+                .or_else(|| self.hub.document.parameters.get(name))
+            {
+                Some(param) => param,
+                None => {
+                    error(name, "is not a parameter of this method".to_string());
+                    continue;
+                }
+            };
+            if !param.repeated && self.params.iter().filter(|p| &p.0 == name).count() > 1 {
+                error(name, "must not be set more than once".to_string());
+            }
+            if !param.enum_values.is_empty() && !param.enum_values.contains(value) {
+                error(
+                    name,
+                    format!("'{}' is not one of {}", value, param.enum_values.join(", ")),
+                );
+            }
+            if param.type_ == "integer" || param.type_ == "number" {
+                let bound = |b: &Option<String>| b.as_ref().and_then(|b| b.parse::<f64>().ok());
+                match value.parse::<f64>() {
+                    Err(_) => error(name, format!("'{}' is not a number", value)),
+                    Ok(v) if bound(&param.minimum).map(|min| v < min).unwrap_or(false) => error(
+                        name,
+                        format!("{} is less than {}", v, param.minimum.as_ref().unwrap()),
+                    ),
+                    Ok(v) if bound(&param.maximum).map(|max| v > max).unwrap_or(false) => error(
+                        name,
+                        format!("{} is greater than {}", v, param.maximum.as_ref().unwrap()),
+                    ),
+                    Ok(_) => {}
+                }
+            }
+            #[cfg(feature = "validation")]
+            if let Some(ref pattern) = param.pattern {
+                if let Some(re) = crate::validation::compiled_pattern(pattern) {
+                    if !re.is_match(value) {
+                        error(name, format!("'{}' doesn't match '{}'", value, pattern));
+                    }
+                }
+            }
+        }
+
+        let mut required: Vec<&String> = self
+            .method
+            .parameters
+            .iter()
+            .filter(|(name, param)| param.required && !self.params.iter().any(|p| &p.0 == *name))
+            .map(|(name, _)| name)
+            .collect();
+        required.sort();
+        for name in required {
+            error(name, "is required".to_string());
+        }
+        if self.method.request.is_some() && self.request.is_none() && !is_media_upload {
+            error("request", "is required".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(errors))
+        }
+    }
+
+    /// Perform the call, and return the response along with its decoded json body.
+    /// The body is `null` if the method doesn't return anything, or if something other than json was requested.
+    pub async fn doit(self) -> Result<(ResponseMeta, json::Value)> {
+        self.execute(None).await
+    }
+
+    /// Like `doit()`, but performs the call with a clone of this one, which can be used again, for instance
+    /// to poll a resource. As the delegate set with `delegate()` can't be part of the clone, it fails with
+    /// `Error::BorrowedDelegate` if there is one, see `shared_delegate()` instead.
+    pub async fn doit_ref(&self) -> Result<(ResponseMeta, json::Value)> {
+        if self.delegate.is_some() {
+            return Err(Error::BorrowedDelegate);
+        }
+        self.clone().doit().await
+    }
+
+    /// Perform the call, uploading the media of the given type in a single request.
+    pub async fn upload<RS>(
+        self,
+        stream: RS,
+        mime_type: Mime,
+    ) -> Result<(ResponseMeta, json::Value)>
+    where
+        RS: ReadSeek,
+    {
+        let mut stream = stream;
+        self.execute(Some((&mut stream, mime_type, "simple"))).await
+    }
+
+    /// Perform the call, uploading the media of the given type as the raw body of a single request.
+    /// The request value, if set, is not sent.
+    pub async fn upload_media<RS>(
+        self,
+        stream: RS,
+        mime_type: Mime,
+    ) -> Result<(ResponseMeta, json::Value)>
+    where
+        RS: ReadSeek,
+    {
+        let mut stream = stream;
+        self.execute(Some((&mut stream, mime_type, "media"))).await
+    }
+
+    /// Perform the call, uploading the media of the given type in chunks, which can be resumed after failures.
+    pub async fn upload_resumable<RS>(
+        self,
+        resumeable_stream: RS,
+        mime_type: Mime,
+    ) -> Result<(ResponseMeta, json::Value)>
+    where
+        RS: ReadSeek,
+    {
+        let mut stream = resumeable_stream;
+        self.execute(Some((&mut stream, mime_type, "resumable")))
+            .await
+    }
+
+    async fn execute(
+        self,
+        media: Option<(&mut dyn ReadSeek, Mime, &'static str)>,
+    ) -> Result<(ResponseMeta, json::Value)> {
+        self.check(media.as_ref().map(|m| m.2) == Some("media"))?;
+        let DynamicCall {
+            hub,
+            method,
+            params,
+            request,
+            delegate,
+            shared_delegate,
+            mut scopes,
+        } = self;
+
+        let mut dd = DefaultDelegate;
+        let mut shared_dlg;
+        let dlg: &mut dyn AsyncDelegate = match (delegate, shared_delegate.as_ref()) {
+            (Some(d), _) => d,
+            (None, Some(shared)) => {
+                shared_dlg = shared.lock().await;
+                &mut *shared_dlg
+            }
+            (None, None) => &mut dd,
+        };
+        let started_at = Instant::now();
+        let http_method = hyper::Method::from_bytes(method.http_method.as_bytes())
+            .expect("http method of discovery document to be valid");
+        dlg.begin(MethodInfo {
+            id: static_method_id(&method.id),
+            http_method: http_method.clone(),
+        })
+        .await;
+
+        let mut params: Vec<(&str, String)> = params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect();
+        if !params.iter().any(|p| p.0 == "alt") {
+            params.push(("alt", "json".to_string()));
+        }
+
+        let (media, url) = match media {
+            Some((reader, mime_type, protocol)) => {
+                let upload = method
+                    .media_upload
+                    .as_ref()
+                    .filter(|_| method.supports_media_upload);
+                // raw media is uploaded to the endpoint of the simple protocol
+                let endpoint = if protocol == "media" {
+                    "simple"
+                } else {
+                    protocol
+                };
+                let path = match upload.and_then(|u| u.protocols.get(endpoint)) {
+                    Some(p) => p.path.clone(),
+                    None => {
+                        dlg.finished(false).await;
+                        return Err(Error::Validation(vec![ValidationError {
+                            field: "uploadType".to_string(),
+                            message: format!("the method doesn't support {} uploads", protocol),
+                        }]));
+                    }
+                };
+                let size = reader.seek(SeekFrom::End(0)).unwrap();
+                reader.seek(SeekFrom::Start(0)).unwrap();
+                let max_size = upload
+                    .and_then(|u| u.max_size.as_ref())
+                    .and_then(|s| size_to_bytes(s))
+                    .unwrap_or(0);
+                let accept: Vec<&str> = upload
+                    .map(|u| u.accept.iter().map(String::as_str).collect())
+                    .unwrap_or_default();
+                if let Err(err) = check_upload(size, max_size, &mime_type, &accept) {
+                    dlg.finished(false).await;
+                    return Err(err);
+                }
+                let upload_type = match protocol {
+                    "simple" if request.is_some() => "multipart",
+                    "simple" => "media",
+                    _ => protocol,
+                };
+                params.push(("uploadType", upload_type.to_string()));
+                (
+                    Some((reader, mime_type, protocol, size)),
+                    hub._root_url.clone() + path.trim_start_matches('/'),
+                )
+            }
+            None => (None, hub._base_url.clone() + &method.path),
+        };
+
+        let url = expand_uri_template(&url, &params);
+        params.retain(|p| {
+            method
+                .parameters
+                .get(p.0)
+                .map(|param| param.location != "path")
+                .unwrap_or(true)
+        });
+
+        if scopes.is_empty() {
+            scopes.extend(method.minimal_scope().map(str::to_string));
+        }
+        if scopes.is_empty() {
+            let key = match hub.auth.api_key() {
+                Some(key) => Some(key),
+                None => dlg.api_key().await,
+            };
+            if let Some(key) = key {
+                params.push(("key", key));
+            }
+        }
+        let url = url::Url::parse_with_params(&url, params).unwrap();
+
+        // raw media is uploaded without the request value
+        let request = request.filter(|_| media.as_ref().map(|m| m.2) != Some("media"));
+        let request_body = request.map(|value| json::to_vec(&value).expect("serde to work"));
+        let json_mime_type: Mime = "application/json".parse().unwrap();
+        let mut media = media;
+
+        loop {
+            if let Some(ref limiter) = hub._rate_limiter {
+                limiter.acquire(&method.id).await;
+            }
+            let token = if scopes.is_empty() {
+                None
+            } else {
+                let scopes: Vec<&str> = scopes.iter().map(String::as_str).collect();
+                match hub.auth.token(&scopes).await {
+                    Ok(token) => Some(token),
+                    Err(err) => match dlg.token(&err).await {
+                        Some(token) => Some(token),
+                        None => {
+                            dlg.finished(false).await;
+                            return Err(Error::MissingToken(err));
+                        }
+                    },
+                }
+            };
+
+            let mut req_builder = hyper::Request::builder()
+                .method(http_method.clone())
+                .uri(url.clone().into_string())
+                .header(USER_AGENT, hub._user_agent.clone());
+            if let Some(ref token) = token {
+                req_builder =
+                    req_builder.header(AUTHORIZATION, format!("Bearer {}", token.as_str()));
+            }
+            let request = match (media.as_mut(), request_body.as_ref()) {
+                (Some((reader, mime_type, "simple" | "media", size)), body) => {
+                    reader.seek(SeekFrom::Start(0)).unwrap();
+                    let mut bytes = Vec::new();
+                    match body {
+                        Some(body) => {
+                            let mut body_reader = io::Cursor::new(body.clone());
+                            let mut mp_reader = MultiPartReader::default();
+                            mp_reader
+                                .add_part(
+                                    &mut body_reader,
+                                    body.len() as u64,
+                                    json_mime_type.clone(),
+                                )
+                                .add_part(&mut *reader, *size, mime_type.clone());
+                            req_builder = req_builder
+                                .header(CONTENT_TYPE, format!("{}", mp_reader.mime_type()));
+                            mp_reader.read_to_end(&mut bytes).unwrap();
+                        }
+                        None => {
+                            req_builder =
+                                req_builder.header(CONTENT_TYPE, format!("{}", mime_type));
+                            reader.read_to_end(&mut bytes).unwrap();
+                        }
+                    }
+                    req_builder.body(hyper::body::Body::from(bytes))
+                }
+                (media, body) => {
+                    if let Some((_, mime_type, _, _)) = media {
+                        req_builder =
+                            req_builder.header("X-Upload-Content-Type", format!("{}", mime_type));
+                    }
+                    match body {
+                        Some(body) => req_builder
+                            .header(CONTENT_TYPE, format!("{}", json_mime_type))
+                            .header(CONTENT_LENGTH, body.len() as u64)
+                            .body(hyper::body::Body::from(body.clone())),
+                        None => req_builder.body(hyper::body::Body::empty()),
+                    }
+                }
+            };
+
+            dlg.pre_request().await;
+            let res = match hub.client.request(request.unwrap()).await {
+                Ok(res) => res,
+                Err(err) => {
+                    if let Retry::After(d) = dlg.http_error(&err).await {
+                        tokio::time::sleep(d).await;
+                        continue;
+                    }
+                    dlg.finished(false).await;
+                    return Err(Error::HttpError(err));
+                }
+            };
+
+            let (res_parts, res_body) = res.into_parts();
+            let res_body_bytes = match hyper::body::to_bytes(res_body).await {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    dlg.finished(false).await;
+                    return Err(Error::HttpError(err));
+                }
+            };
+            let mut res_body_string = String::from_utf8_lossy(&res_body_bytes).into_owned();
+            let mut reconstructed_result =
+                hyper::Response::from_parts(res_parts, res_body_string.clone().into());
+
+            if !reconstructed_result.status().is_success() {
+                let json_server_error = json::from_str::<JsonServerError>(&res_body_string).ok();
+                let server_error = json::from_str::<ServerError>(&res_body_string)
+                    .or_else(|_| json::from_str::<ErrorResponse>(&res_body_string).map(|r| r.error))
+                    .ok();
+                if let (Some(limiter), Some(err)) =
+                    (hub._rate_limiter.as_ref(), server_error.as_ref())
+                {
+                    limiter.record(&method.id, Some(err));
+                }
+                if let Retry::After(d) = dlg
+                    .http_failure(&reconstructed_result, json_server_error, server_error)
+                    .await
+                {
+                    tokio::time::sleep(d).await;
+                    continue;
+                }
+                dlg.finished(false).await;
+                return match json::from_str::<ErrorResponse>(&res_body_string) {
+                    Err(_) => Err(Error::Failure(reconstructed_result)),
+                    Ok(serr) => Err(Error::BadRequest(serr)),
+                };
+            }
+
+            if let Some((reader, mime_type, "resumable", size)) = media.as_mut() {
+                let upload_url = match reconstructed_result
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|l| l.to_str().ok())
+                {
+                    Some(url) => url.to_string(),
+                    None => {
+                        dlg.finished(false).await;
+                        return Err(Error::Failure(reconstructed_result));
+                    }
+                };
+                dlg.store_upload_url(Some(&upload_url)).await;
+                let mut client = hub.client.clone();
+                let upload_result = ResumableUploadHelper {
+                    client: &mut client,
+                    delegate: &mut *dlg,
+                    start_at: Some(0),
+                    user_agent: &hub._user_agent,
+                    auth_header: token
+                        .as_ref()
+                        .map(|t| format!("Bearer {}", t.as_str()))
+                        .unwrap_or_default(),
+                    url: &upload_url,
+                    reader: &mut **reader,
+                    media_type: mime_type.clone(),
+                    content_length: *size,
+                }
+                .upload()
+                .await;
+                match upload_result {
+                    None => {
+                        dlg.finished(false).await;
+                        return Err(Error::Cancelled);
+                    }
+                    Some(Err(err)) => {
+                        dlg.finished(false).await;
+                        return Err(Error::HttpError(err));
+                    }
+                    Some(Ok(res)) => {
+                        if !res.status().is_success() {
+                            dlg.store_upload_url(None).await;
+                            dlg.finished(false).await;
+                            return Err(Error::Failure(res));
+                        }
+                        let (res_parts, res_body) = res.into_parts();
+                        let bytes = match hyper::body::to_bytes(res_body).await {
+                            Ok(bytes) => bytes,
+                            Err(err) => {
+                                dlg.finished(false).await;
+                                return Err(Error::HttpError(err));
+                            }
+                        };
+                        res_body_string = String::from_utf8_lossy(&bytes).into_owned();
+                        reconstructed_result =
+                            hyper::Response::from_parts(res_parts, res_body_string.clone().into());
+                    }
+                }
+            }
+
+            let is_json = reconstructed_result
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.starts_with("application/json"))
+                .unwrap_or(true);
+            let value = if res_body_string.trim().is_empty() || !is_json {
+                json::Value::Null
+            } else {
+                match json::from_str(&res_body_string) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        dlg.response_json_decode_error(&res_body_string, &err).await;
+                        dlg.finished(false).await;
+                        return Err(Error::JsonDecodeError(res_body_string, err));
+                    }
+                }
+            };
+            if let Some(ref limiter) = hub._rate_limiter {
+                limiter.record(&method.id, None);
+            }
+            dlg.finished(true).await;
+            return Ok((
+                ResponseMeta::new(reconstructed_result, started_at.elapsed()),
+                value,
+            ));
+        }
+    }
+}
@@ -0,0 +1,1444 @@
+//! The client code shared by all crates generated by [google-apis-rs](https://github.com/Byron/google-apis-rs).
+//!
+//! As all hubs use the types of this crate, a `Delegate`, an `Error` handler or an authenticator
+//! works with every API. Each generated crate re-exports it as its `client` module.
+#[macro_use]
+extern crate serde_derive;
+extern crate yup_oauth2 as oauth2;
+
+pub mod auth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+pub mod delegate;
+pub mod download;
+pub mod dynamic;
+pub mod ratelimit;
+#[cfg(feature = "validation")]
+pub mod validation;
+
+pub use auth::*;
+#[cfg(feature = "blocking")]
+pub use blocking::*;
+pub use cache::*;
+pub use delegate::*;
+pub use download::*;
+pub use dynamic::*;
+pub use ratelimit::*;
+#[cfg(feature = "validation")]
+pub use validation::*;
+
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use hyper::client::connect::HttpConnector;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
+use hyper::Method;
+use hyper::StatusCode;
+use hyper_rustls::HttpsConnector;
+
+use mime::{Attr, Mime, SubLevel, TopLevel, Value};
+
+use serde_json as json;
+
+const LINE_ENDING: &str = "\r\n";
+
+pub enum Retry {
+    /// Signal you don't want to retry
+    Abort,
+    /// Signals you want to retry after the given duration
+    After(Duration),
+}
+
+/// Identifies the Hub. There is only one per library, this trait is supposed
+/// to make intended use more explicit.
+/// The hub allows to access all resource methods more easily.
+pub trait Hub {}
+
+/// Identifies types for building methods of a particular resource type
+pub trait MethodsBuilder {}
+
+/// Identifies types which represent builders for a particular resource method
+pub trait CallBuilder {}
+
+/// Identifies types which can be inserted and deleted.
+/// Types with this trait are most commonly used by clients of this API.
+pub trait Resource {}
+
+/// Identifies types which are used in API responses.
+pub trait ResponseResult {}
+
+/// Identifies types which are used in API requests.
+pub trait RequestValue {}
+
+/// Identifies types which are not actually used by the API
+/// This might be a bug within the google API schema.
+pub trait UnusedType {}
+
+/// Identifies types which are only used as part of other types, which
+/// usually are carrying the `Resource` trait.
+pub trait Part {}
+
+/// Identifies types which are only used by other types internally.
+/// They have no special meaning, this trait just marks them for completeness.
+pub trait NestedType {}
+
+/// A utility to specify reader types which provide seeking capabilities too
+pub trait ReadSeek: Seek + Read {}
+impl<T: Seek + Read> ReadSeek for T {}
+
+/// A trait for all types that can convert themselves into a *parts* string
+pub trait ToParts {
+    fn to_parts(&self) -> String;
+}
+
+/// A utility type which can decode a server response that indicates error
+#[derive(Deserialize, Clone)]
+pub struct JsonServerError {
+    pub error: String,
+    pub error_description: Option<String>,
+}
+
+/// A utility to represent detailed errors we might see in case there are BadRequests.
+/// The latter happen if the sent parameters or request structures are unsound
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ErrorResponse {
+    pub error: ServerError,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ServerError {
+    pub errors: Vec<ServerMessage>,
+    pub code: u16,
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ServerMessage {
+    pub domain: String,
+    pub reason: String,
+    pub message: String,
+    #[serde(rename = "locationType")]
+    pub location_type: Option<String>,
+    pub location: Option<String>,
+}
+
+/// A trait specifying functionality to help controlling any request performed by the API.
+/// The trait has a conservative default implementation.
+///
+/// It contains methods to deal with all common issues, as well with the ones related to
+/// uploading media
+pub trait Delegate {
+    /// Called at the beginning of any API request. The delegate should store the method
+    /// information if he is interesting in knowing more context when further calls to it
+    /// are made.
+    /// The matching `finished()` call will always be made, no matter whether or not the API
+    /// request was successful. That way, the delegate may easily maintain a clean state
+    /// between various API calls.
+    fn begin(&mut self, _info: MethodInfo) {}
+
+    /// Called whenever there is an [HttpError](hyper::Error), usually if there are network problems.
+    ///
+    /// If you choose to retry after a duration, the duration should be chosen using the
+    /// [exponential backoff algorithm](http://en.wikipedia.org/wiki/Exponential_backoff).
+    ///
+    /// Return retry information.
+    fn http_error(&mut self, _err: &hyper::Error) -> Retry {
+        Retry::Abort
+    }
+
+    /// Called whenever there is the need for your applications API key after
+    /// the official authenticator implementation didn't provide one, for some reason.
+    /// If this method returns None as well, the underlying operation will fail
+    fn api_key(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Called whenever the Authenticator didn't yield a token. The delegate
+    /// may attempt to provide one, or just take it as a general information about the
+    /// impending failure.
+    /// The given Error provides information about why the token couldn't be acquired in the
+    /// first place
+    fn token(&mut self, err: &oauth2::Error) -> Option<oauth2::AccessToken> {
+        let _ = err;
+        None
+    }
+
+    /// Called during resumable uploads to provide a URL for the impending upload.
+    /// It was saved after a previous call to `store_upload_url(...)`, and if not None,
+    /// will be used instead of asking the server for a new upload URL.
+    /// This is useful in case a previous resumable upload was aborted/canceled, but should now
+    /// be resumed.
+    /// The returned URL will be used exactly once - if it fails again and the delegate allows
+    /// to retry, we will ask the server for a new upload URL.
+    fn upload_url(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Called after we have retrieved a new upload URL for a resumable upload to store it
+    /// in case we fail or cancel. That way, we can attempt to resume the upload later,
+    /// see `upload_url()`.
+    /// It will also be called with None after a successful upload, which allows the delegate
+    /// to forget the URL. That way, we will not attempt to resume an upload that has already
+    /// finished.
+    fn store_upload_url(&mut self, url: Option<&str>) {
+        let _ = url;
+    }
+
+    /// Called whenever a server response could not be decoded from json.
+    /// It's for informational purposes only, the caller will return with an error
+    /// accordingly.
+    ///
+    /// # Arguments
+    ///
+    /// * `json_encoded_value` - The json-encoded value which failed to decode.
+    /// * `json_decode_error`  - The decoder error
+    fn response_json_decode_error(
+        &mut self,
+        json_encoded_value: &str,
+        json_decode_error: &json::Error,
+    ) {
+        let _ = json_encoded_value;
+        let _ = json_decode_error;
+    }
+
+    /// Called whenever the http request returns with a non-success status code.
+    /// This can involve authentication issues, or anything else that very much
+    /// depends on the used API method.
+    /// The delegate should check the status, header and decoded json error to decide
+    /// whether to retry or not. In the latter case, the underlying call will fail.
+    ///
+    /// If you choose to retry after a duration, the duration should be chosen using the
+    /// [exponential backoff algorithm](http://en.wikipedia.org/wiki/Exponential_backoff).
+    fn http_failure(
+        &mut self,
+        _: &hyper::Response<hyper::body::Body>,
+        _err: Option<JsonServerError>,
+        _: Option<ServerError>,
+    ) -> Retry {
+        Retry::Abort
+    }
+
+    /// Called prior to sending the main request of the given method. It can be used to time
+    /// the call or to print progress information.
+    /// It's also useful as you can be sure that a request will definitely be made.
+    fn pre_request(&mut self) {}
+
+    /// Return the size of each chunk of a resumable upload or a download.
+    /// Must be a power of two, with 1<<18 being the smallest allowed chunk size for uploads.
+    /// Will be called once before starting any resumable upload or download.
+    fn chunk_size(&mut self) -> u64 {
+        1 << 23
+    }
+
+    /// Called before the given chunk is uploaded to the server.
+    /// If true is returned, the upload will be interrupted.
+    /// However, it may be resumable if you stored the upload URL in a previous call
+    /// to `store_upload_url()`
+    fn cancel_chunk_upload(&mut self, chunk: &ContentRange) -> bool {
+        let _ = chunk;
+        false
+    }
+
+    /// Called before the given chunk is downloaded from the server, which makes it suitable
+    /// to report progress. Its total length is 0 until the server told it.
+    /// If true is returned, the download will be interrupted. Whatever was written so far
+    /// may be used to resume it later.
+    fn cancel_chunk_download(&mut self, chunk: &ContentRange) -> bool {
+        let _ = chunk;
+        false
+    }
+
+    /// Return the amount of ranges a download is split into, which are downloaded in parallel.
+    /// Will be called once before starting any download.
+    fn download_ranges(&mut self) -> usize {
+        1
+    }
+
+    /// Called before the API request method returns, in every case. It can be used to clean up
+    /// internal state between calls to the API.
+    /// This call always has a matching call to `begin(...)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_success` - a true value indicates the operation was successful. If false, you should
+    ///   discard all values stored during `store_upload_url`.
+    fn finished(&mut self, is_success: bool) {
+        let _ = is_success;
+    }
+}
+
+/// A delegate with a conservative default implementation, which is used if no other delegate is
+/// set.
+#[derive(Default)]
+pub struct DefaultDelegate;
+
+impl Delegate for DefaultDelegate {}
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    /// The http connection failed
+    HttpError(hyper::Error),
+
+    /// An attempt was made to upload a resource with size stored in field `.0`
+    /// even though the maximum upload size is what is stored in field `.1`.
+    UploadSizeLimitExceeded(u64, u64),
+
+    /// An attempt was made to upload media of the type stored in field `.0`,
+    /// even though the method only accepts the types stored in field `.1`.
+    UploadMimeTypeNotAccepted(Mime, Vec<String>),
+
+    /// Downloaded media was expected to have the checksum stored in field `.0`,
+    /// but has the one stored in field `.1`.
+    ChecksumMismatch(Checksum, Checksum),
+
+    /// Reading or writing media failed
+    Io(io::Error),
+
+    /// Represents information about a request that was not understood by the server.
+    /// Details are included.
+    BadRequest(ErrorResponse),
+
+    /// We needed an API key for authentication, but didn't obtain one.
+    /// Neither through the authenticator, nor through the Delegate.
+    MissingAPIKey,
+
+    /// We required a Token, but didn't get one from the Authenticator
+    MissingToken(oauth2::Error),
+
+    /// The delgate instructed to cancel the operation
+    Cancelled,
+
+    /// An additional, free form field clashed with one of the built-in optional ones
+    FieldClash(&'static str),
+
+    /// Shows that we failed to decode the server response.
+    /// This can happen if the protocol changes in conjunction with strict json decoding.
+    JsonDecodeError(String, json::Error),
+
+    /// Indicates an HTTP repsonse with a non-success status code
+    Failure(hyper::Response<hyper::body::Body>),
+
+    /// Parameters or the request value don't meet the constraints of the API, and were not sent
+    Validation(Vec<ValidationError>),
+
+    /// The hub is in read-only mode, and refused to call the method with the given id, as it may change data
+    /// or no read-only scope authorizes it
+    ReadOnly(&'static str),
+
+    /// The resource didn't change since it had the `ETag` sent with `If-None-Match`, and there was no cached
+    /// value of it. The response is stored in field `.0`.
+    NotModified(hyper::Response<hyper::body::Body>),
+
+    /// The resource changed since it had the `ETag` sent with `If-Match`, and the call was refused.
+    /// The response is stored in field `.0`.
+    PreconditionFailed(hyper::Response<hyper::body::Body>),
+
+    /// A call was to be made with a clone of its builder, like by `doit_ref()`, but the builder has a delegate set
+    /// with `delegate()`, which is borrowed and can't be part of a clone. Use `shared_delegate()` instead.
+    BorrowedDelegate,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::HttpError(ref err) => err.fmt(f),
+            Error::UploadSizeLimitExceeded(ref resource_size, ref max_size) => writeln!(
+                f,
+                "The media size {} exceeds the maximum allowed upload size of {}",
+                resource_size, max_size
+            ),
+            Error::ChecksumMismatch(ref expected, ref actual) => writeln!(
+                f,
+                "The downloaded media has the checksum {}, but {} was expected",
+                actual, expected
+            ),
+            Error::Io(ref err) => err.fmt(f),
+            Error::UploadMimeTypeNotAccepted(ref mime_type, ref accept) => writeln!(
+                f,
+                "The media type '{}' is not accepted, it must be one of {}",
+                mime_type,
+                accept.join(", ")
+            ),
+            Error::MissingAPIKey => {
+                (writeln!(
+                    f,
+                    "The application's API key was not found in the configuration"
+                ))
+                .ok();
+                writeln!(
+                    f,
+                    "It is used as there are no Scopes defined for this method."
+                )
+            }
+            Error::BadRequest(ref err) => {
+                writeln!(f, "Bad Request ({}): {}", err.error.code, err.error.message)?;
+                for err in err.error.errors.iter() {
+                    writeln!(
+                        f,
+                        "    {}: {}, {}{}",
+                        err.domain,
+                        err.message,
+                        err.reason,
+                        match &err.location {
+                            Some(loc) => format!("@{}", loc),
+                            None => String::new(),
+                        }
+                    )?;
+                }
+                Ok(())
+            }
+            Error::MissingToken(ref err) => {
+                writeln!(f, "Token retrieval failed with error: {}", err)
+            }
+            Error::Cancelled => writeln!(f, "Operation cancelled by delegate"),
+            Error::FieldClash(field) => writeln!(
+                f,
+                "The custom parameter '{}' is already provided natively by the CallBuilder.",
+                field
+            ),
+            Error::JsonDecodeError(ref json_str, ref err) => writeln!(f, "{}: {}", err, json_str),
+            Error::Failure(ref response) => {
+                writeln!(f, "Http status indicates failure: {:?}", response)
+            }
+            Error::Validation(ref errors) => {
+                writeln!(f, "Validation failed:")?;
+                for err in errors.iter() {
+                    writeln!(f, "    {}", err)?;
+                }
+                Ok(())
+            }
+            Error::ReadOnly(method) => writeln!(
+                f,
+                "The method '{}' may change data, and was refused by the read-only hub",
+                method
+            ),
+            Error::NotModified(_) => writeln!(f, "The resource was not modified"),
+            Error::PreconditionFailed(_) => writeln!(
+                f,
+                "The resource was modified, and doesn't have the expected ETag anymore"
+            ),
+            Error::BorrowedDelegate => writeln!(
+                f,
+                "The call builder has a borrowed delegate, and can't be cloned to make the call"
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::HttpError(ref err) => err.source(),
+            Error::JsonDecodeError(_, ref err) => err.source(),
+            Error::Io(ref err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+/// A value which doesn't meet the constraints the API documents for it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    /// The name of the parameter, or the path to the field of the request value, like `request.labels[team]`.
+    pub field: String,
+    /// What is wrong with the value.
+    pub message: String,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// A universal result type used as return for all calls.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// What the server responded with, besides the decoded result of a call.
+///
+/// The body of the response was read by the call already. The response is kept as a whole, with the body as string,
+/// and is available through `response()` and `into_response()`.
+#[derive(Debug)]
+pub struct ResponseMeta {
+    response: hyper::Response<hyper::body::Body>,
+    elapsed: Option<Duration>,
+}
+
+impl ResponseMeta {
+    /// Wrap the response of a call which took `elapsed` from its beginning until the response was read,
+    /// including all retries.
+    pub fn new(response: hyper::Response<hyper::body::Body>, elapsed: Duration) -> ResponseMeta {
+        ResponseMeta {
+            response,
+            elapsed: Some(elapsed),
+        }
+    }
+
+    /// Describe a call served from the cache, after the server answered with `not_modified`, a *304 Not Modified*.
+    /// The response has status 200 and the `ETag` and body of `cached`, along with the other headers of `not_modified`.
+    pub fn from_cache(
+        not_modified: hyper::Response<hyper::body::Body>,
+        cached: &CachedResponse,
+        elapsed: Duration,
+    ) -> ResponseMeta {
+        let (mut parts, _) = not_modified.into_parts();
+        parts.status = StatusCode::OK;
+        if let Ok(etag) = HeaderValue::from_str(&cached.etag) {
+            parts.headers.insert(hyper::header::ETAG, etag);
+        }
+        ResponseMeta::new(
+            hyper::Response::from_parts(parts, cached.body.clone().into()),
+            elapsed,
+        )
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.response.status()
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        self.response.headers()
+    }
+
+    /// The id the server gave the request with the `x-goog-request-id` header, which helps Google's support to find it.
+    pub fn request_id(&self) -> Option<&str> {
+        self.header("x-goog-request-id")
+    }
+
+    /// The id of an upload, as given by the `x-guploader-uploadid` header.
+    pub fn upload_id(&self) -> Option<&str> {
+        self.header("x-guploader-uploadid")
+    }
+
+    /// The `ETag` of the resource, to make calls conditional on it.
+    pub fn etag(&self) -> Option<&str> {
+        self.header("etag")
+    }
+
+    /// The time from the beginning of the call until its response was read, if known.
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.elapsed
+    }
+
+    /// The value of the given header, if it is set and valid UTF-8.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+    }
+
+    /// The response, with the body as it was received.
+    pub fn response(&self) -> &hyper::Response<hyper::body::Body> {
+        &self.response
+    }
+
+    pub fn into_response(self) -> hyper::Response<hyper::body::Body> {
+        self.response
+    }
+}
+
+/// Wrap a response whose timing isn't known, like one decoded by `parse_response()` of a call builder.
+impl From<hyper::Response<hyper::body::Body>> for ResponseMeta {
+    fn from(response: hyper::Response<hyper::body::Body>) -> ResponseMeta {
+        ResponseMeta {
+            response,
+            elapsed: None,
+        }
+    }
+}
+
+/// Contains information about an API request.
+#[derive(Clone)]
+pub struct MethodInfo {
+    pub id: &'static str,
+    pub http_method: Method,
+}
+
+/// Check the media of an upload against the constraints of the method before sending it.
+///
+/// `max_size` is the maximum amount of bytes, or 0 if there is no limit, and `accept` lists the
+/// accepted media types, like `image/*` or `*/*`. If it is empty, all types are accepted.
+#[allow(clippy::result_large_err)]
+pub fn check_upload(size: u64, max_size: u64, mime_type: &Mime, accept: &[&str]) -> Result<()> {
+    if max_size > 0 && size > max_size {
+        return Err(Error::UploadSizeLimitExceeded(size, max_size));
+    }
+    let (top, sub) = (mime_type.0.to_string(), mime_type.1.to_string());
+    let is_accepted = |pattern: &&str| match pattern.split_once('/') {
+        Some((t, s)) => {
+            (t == "*" || t.eq_ignore_ascii_case(&top)) && (s == "*" || s.eq_ignore_ascii_case(&sub))
+        }
+        None => false,
+    };
+    if accept.is_empty() || accept.iter().any(is_accepted) {
+        Ok(())
+    } else {
+        Err(Error::UploadMimeTypeNotAccepted(
+            mime_type.clone(),
+            accept.iter().map(|a| a.to_string()).collect(),
+        ))
+    }
+}
+
+const BOUNDARY: &str = "MDuXWGyeE33QFXGchb2VFWc4Z7945d";
+
+/// Provides a `Read` interface that converts multiple parts into the protocol
+/// identified by [RFC2387](https://tools.ietf.org/html/rfc2387).
+/// **Note**: This implementation is just as rich as it needs to be to perform uploads
+/// to google APIs, and might not be a fully-featured implementation.
+#[derive(Default)]
+pub struct MultiPartReader<'a> {
+    raw_parts: Vec<(String, &'a mut dyn Read)>,
+    current_part: Option<(Cursor<Vec<u8>>, &'a mut dyn Read)>,
+    last_part_boundary: Option<Cursor<Vec<u8>>>,
+}
+
+impl<'a> MultiPartReader<'a> {
+    /// Reserve memory for exactly the given amount of parts
+    pub fn reserve_exact(&mut self, cap: usize) {
+        self.raw_parts.reserve_exact(cap);
+    }
+
+    /// Add a new part to the queue of parts to be read on the first `read` call.
+    ///
+    /// # Arguments
+    ///
+    /// `headers` - identifying the body of the part. It's similar to the header
+    ///             in an ordinary single-part call, and should thus contain the
+    ///             same information.
+    /// `reader`  - a reader providing the part's body
+    /// `size`    - the amount of bytes provided by the reader. It will be put onto the header as
+    ///             content-size.
+    /// `mime`    - It will be put onto the content type
+    pub fn add_part(
+        &mut self,
+        reader: &'a mut dyn Read,
+        size: u64,
+        mime_type: Mime,
+    ) -> &mut MultiPartReader<'a> {
+        // headers are written in a fixed order, which keeps the produced body deterministic
+        let headers = format!(
+            "Content-Length: {}{}Content-Type: {}",
+            size, LINE_ENDING, mime_type
+        );
+        self.raw_parts.push((headers, reader));
+        self
+    }
+
+    /// Returns the mime-type representing our multi-part message.
+    /// Use it with the ContentType header.
+    pub fn mime_type(&self) -> Mime {
+        Mime(
+            TopLevel::Multipart,
+            SubLevel::Ext("Related".to_string()),
+            vec![(
+                Attr::Ext("boundary".to_string()),
+                Value::Ext(BOUNDARY.to_string()),
+            )],
+        )
+    }
+
+    /// Returns true if we are totally used
+    fn is_depleted(&self) -> bool {
+        self.raw_parts.is_empty()
+            && self.current_part.is_none()
+            && self.last_part_boundary.is_none()
+    }
+
+    /// Returns true if we are handling our last part
+    fn is_last_part(&self) -> bool {
+        self.raw_parts.is_empty() && self.current_part.is_some()
+    }
+}
+
+impl<'a> Read for MultiPartReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match (
+            self.raw_parts.len(),
+            self.current_part.is_none(),
+            self.last_part_boundary.is_none(),
+        ) {
+            (_, _, false) => {
+                let br = self
+                    .last_part_boundary
+                    .as_mut()
+                    .unwrap()
+                    .read(buf)
+                    .unwrap_or(0);
+                if br < buf.len() {
+                    self.last_part_boundary = None;
+                }
+                return Ok(br);
+            }
+            (0, true, true) => return Ok(0),
+            (n, true, _) if n > 0 => {
+                let (headers, reader) = self.raw_parts.remove(0);
+                let mut c = Cursor::new(Vec::<u8>::new());
+                (write!(
+                    &mut c,
+                    "{}--{}{}{}{}{}",
+                    LINE_ENDING, BOUNDARY, LINE_ENDING, headers, LINE_ENDING, LINE_ENDING,
+                ))
+                .unwrap();
+                c.seek(SeekFrom::Start(0)).unwrap();
+                self.current_part = Some((c, reader));
+            }
+            _ => {}
+        }
+
+        // read headers as long as possible
+        let (hb, rr) = {
+            let &mut (ref mut c, ref mut reader) = self.current_part.as_mut().unwrap();
+            let b = c.read(buf).unwrap_or(0);
+            (b, reader.read(&mut buf[b..]))
+        };
+
+        match rr {
+            Ok(bytes_read) => {
+                if hb < buf.len() && bytes_read == 0 {
+                    if self.is_last_part() {
+                        // before clearing the last part, we will add the boundary that
+                        // will be written last
+                        self.last_part_boundary = Some(Cursor::new(
+                            format!("{}--{}--", LINE_ENDING, BOUNDARY).into_bytes(),
+                        ))
+                    }
+                    // We are depleted - this can trigger the next part to come in
+                    self.current_part = None;
+                }
+                let mut total_bytes_read = hb + bytes_read;
+                while total_bytes_read < buf.len() && !self.is_depleted() {
+                    match self.read(&mut buf[total_bytes_read..]) {
+                        Ok(br) => total_bytes_read += br,
+                        Err(err) => return Err(err),
+                    }
+                }
+                Ok(total_bytes_read)
+            }
+            Err(err) => {
+                // fail permanently
+                self.current_part = None;
+                self.last_part_boundary = None;
+                self.raw_parts.clear();
+                Err(err)
+            }
+        }
+    }
+}
+
+/// A part of a multipart body, as yielded by the `MultiPartParser`.
+#[derive(Debug, Default)]
+pub struct MultiPart {
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl MultiPart {
+    /// Returns the content type of the part, if it has a valid one.
+    pub fn mime_type(&self) -> Option<Mime> {
+        self.headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+    }
+}
+
+/// Parses a multipart body, like `multipart/mixed` or `multipart/related`, as identified by
+/// [RFC2046](https://tools.ietf.org/html/rfc2046#section-5.1), into its parts.
+///
+/// It is the counterpart of the `MultiPartReader`, and yields the parts one by one while reading
+/// from the underlying reader only as much as needed to find the end of each part.
+/// The preamble and the epilogue are ignored.
+pub struct MultiPartParser<R> {
+    reader: R,
+    /// The delimiter of parts, being a line ending, two dashes and the boundary.
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    eof: bool,
+    started: bool,
+    done: bool,
+}
+
+impl<R: Read> MultiPartParser<R> {
+    /// Create a parser for a body whose parts are separated by the given `boundary`.
+    pub fn new(reader: R, boundary: &str) -> MultiPartParser<R> {
+        MultiPartParser {
+            reader,
+            delimiter: format!("{}--{}", LINE_ENDING, boundary).into_bytes(),
+            // the first delimiter doesn't need to be preceded by a line ending
+            buf: LINE_ENDING.as_bytes().to_vec(),
+            eof: false,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Create a parser using the boundary of the given multipart content type, like
+    /// `multipart/mixed; boundary=batch_foo`. Returns None if it isn't a multipart type or has no boundary.
+    pub fn from_content_type(reader: R, content_type: &str) -> Option<MultiPartParser<R>> {
+        match content_type.parse().ok()? {
+            Mime(TopLevel::Multipart, _, params) => params
+                .iter()
+                .find(|(attr, _)| *attr == Attr::Ext("boundary".to_string()))
+                .map(|(_, value)| MultiPartParser::new(reader, value.as_str().trim_matches('"'))),
+            _ => None,
+        }
+    }
+
+    /// Returns the next part, or None once the closing delimiter was seen.
+    pub fn next_part(&mut self) -> io::Result<Option<MultiPart>> {
+        if self.done {
+            return Ok(None);
+        }
+        let delimiter = self.delimiter.clone();
+        if !self.started {
+            // skip the preamble
+            let at = self.find(&delimiter)?;
+            self.buf.drain(..at + delimiter.len());
+            self.started = true;
+        }
+
+        // after a delimiter, two dashes close the body
+        self.fill(2)?;
+        if self.buf.starts_with(b"--") {
+            self.done = true;
+            return Ok(None);
+        }
+        // skip the transport padding up to the end of the delimiter line
+        let at = self.find(LINE_ENDING.as_bytes())?;
+        if self.buf[..at].iter().any(|b| *b != b' ' && *b != b'\t') {
+            return Err(invalid_data(
+                "unexpected characters after a multipart boundary",
+            ));
+        }
+        self.buf.drain(..at + LINE_ENDING.len());
+
+        // the headers end with an empty line, which directly follows the delimiter line without headers
+        let headers_end = if self.fill(2)? && self.buf.starts_with(LINE_ENDING.as_bytes()) {
+            0
+        } else {
+            self.find(b"\r\n\r\n")? + LINE_ENDING.len()
+        };
+        let headers = parse_part_headers(&self.buf[..headers_end])?;
+        self.buf.drain(..headers_end + LINE_ENDING.len());
+
+        let at = self.find(&delimiter)?;
+        let body = self.buf.drain(..at).collect();
+        self.buf.drain(..delimiter.len());
+        Ok(Some(MultiPart { headers, body }))
+    }
+
+    /// Read from the underlying reader until the buffer holds at least `len` bytes.
+    /// Returns false if the reader ended before.
+    fn fill(&mut self, len: usize) -> io::Result<bool> {
+        let mut chunk = [0u8; 8192];
+        while self.buf.len() < len && !self.eof {
+            match self.reader.read(&mut chunk)? {
+                0 => self.eof = true,
+                n => self.buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+        Ok(self.buf.len() >= len)
+    }
+
+    /// Returns the position of `needle` in the buffer, reading as much as needed to find it.
+    fn find(&mut self, needle: &[u8]) -> io::Result<usize> {
+        let mut from = 0;
+        loop {
+            if let Some(at) = self.buf[from..]
+                .windows(needle.len())
+                .position(|w| w == needle)
+            {
+                return Ok(from + at);
+            }
+            // a match may start within the tail of what was searched already
+            from = (self.buf.len() + 1).saturating_sub(needle.len());
+            let len = self.buf.len();
+            if !self.fill(len + 1)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "multipart body ended before its closing boundary",
+                ));
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for MultiPartParser<R> {
+    type Item = io::Result<MultiPart>;
+
+    fn next(&mut self) -> Option<io::Result<MultiPart>> {
+        match self.next_part() {
+            Ok(part) => part.map(Ok),
+            Err(err) => {
+                // fail permanently
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Parse the header lines of a part, unfolding continuation lines.
+fn parse_part_headers(raw: &[u8]) -> io::Result<HeaderMap> {
+    let raw = std::str::from_utf8(raw)
+        .map_err(|_| invalid_data("multipart headers are not valid utf-8"))?;
+    let mut lines: Vec<String> = Vec::new();
+    for line in raw.split(LINE_ENDING).filter(|l| !l.is_empty()) {
+        match lines.last_mut() {
+            Some(last) if line.starts_with(' ') || line.starts_with('\t') => {
+                last.push(' ');
+                last.push_str(line.trim());
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid_data("multipart header without a colon"))?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| invalid_data("invalid multipart header name"))?;
+        let value = HeaderValue::from_str(value.trim())
+            .map_err(|_| invalid_data("invalid multipart header value"))?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// The `X-Upload-Content-Type` header.
+///
+/// Generated via rustc --pretty expanded -Z unstable-options, and manually
+/// processed to be more readable.
+#[derive(PartialEq, Debug, Clone)]
+pub struct XUploadContentType(pub Mime);
+
+impl ::std::ops::Deref for XUploadContentType {
+    type Target = Mime;
+    fn deref(&self) -> &Mime {
+        &self.0
+    }
+}
+impl ::std::ops::DerefMut for XUploadContentType {
+    fn deref_mut(&mut self) -> &mut Mime {
+        &mut self.0
+    }
+}
+impl Display for XUploadContentType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Chunk {
+    pub first: u64,
+    pub last: u64,
+}
+
+impl fmt::Display for Chunk {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        (write!(fmt, "{}-{}", self.first, self.last)).ok();
+        Ok(())
+    }
+}
+
+impl FromStr for Chunk {
+    type Err = &'static str;
+
+    /// NOTE: only implements `%i-%i`, not `*`
+    fn from_str(s: &str) -> std::result::Result<Chunk, &'static str> {
+        let parts: Vec<&str> = s.split('-').collect();
+        if parts.len() != 2 {
+            return Err("Expected two parts: %i-%i");
+        }
+        Ok(Chunk {
+            first: match FromStr::from_str(parts[0]) {
+                Ok(d) => d,
+                _ => return Err("Couldn't parse 'first' as digit"),
+            },
+            last: match FromStr::from_str(parts[1]) {
+                Ok(d) => d,
+                _ => return Err("Couldn't parse 'last' as digit"),
+            },
+        })
+    }
+}
+
+/// Implements the Content-Range header, for serialization only
+#[derive(Clone, PartialEq, Debug)]
+pub struct ContentRange {
+    pub range: Option<Chunk>,
+    pub total_length: u64,
+}
+
+impl ContentRange {
+    pub fn header_value(&self) -> String {
+        format!(
+            "bytes {}/{}",
+            match self.range {
+                Some(ref c) => format!("{}", c),
+                None => "*".to_string(),
+            },
+            self.total_length
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RangeResponseHeader(pub Chunk);
+
+impl RangeResponseHeader {
+    pub fn from_bytes(raw: &[u8]) -> Self {
+        if !raw.is_empty() {
+            if let Ok(s) = std::str::from_utf8(raw) {
+                const PREFIX: &str = "bytes ";
+                if let Some(stripped) = s.strip_prefix(PREFIX) {
+                    if let Ok(c) = <Chunk as FromStr>::from_str(stripped) {
+                        return RangeResponseHeader(c);
+                    }
+                }
+            }
+        }
+
+        panic!("Unable to parse Range header {:?}", raw)
+    }
+}
+
+/// A utility type to perform a resumable upload from start to end.
+pub struct ResumableUploadHelper<'a> {
+    pub client: &'a mut hyper::client::Client<
+        hyper_rustls::HttpsConnector<hyper::client::connect::HttpConnector>,
+        hyper::body::Body,
+    >,
+    pub delegate: &'a mut dyn AsyncDelegate,
+    pub start_at: Option<u64>,
+    pub user_agent: &'a str,
+    pub auth_header: String,
+    pub url: &'a str,
+    pub reader: &'a mut dyn ReadSeek,
+    pub media_type: Mime,
+    pub content_length: u64,
+}
+
+impl<'a> ResumableUploadHelper<'a> {
+    async fn query_transfer_status(
+        &mut self,
+    ) -> std::result::Result<u64, hyper::Result<hyper::Response<hyper::body::Body>>> {
+        loop {
+            match self
+                .client
+                .request(
+                    hyper::Request::builder()
+                        .method(hyper::Method::PUT)
+                        .uri(self.url)
+                        .header(USER_AGENT, self.user_agent.to_string())
+                        .header(
+                            "Content-Range",
+                            ContentRange {
+                                range: None,
+                                total_length: self.content_length,
+                            }
+                            .header_value(),
+                        )
+                        .header(AUTHORIZATION, self.auth_header.clone())
+                        .body(hyper::body::Body::empty())
+                        .unwrap(),
+                )
+                .await
+            {
+                Ok(r) => {
+                    // 308 = resume-incomplete == PermanentRedirect
+                    let headers = r.headers().clone();
+                    let h: RangeResponseHeader = match headers.get("Range") {
+                        Some(hh) if r.status() == StatusCode::PERMANENT_REDIRECT => {
+                            RangeResponseHeader::from_bytes(hh.as_bytes())
+                        }
+                        None | Some(_) => {
+                            if let Retry::After(d) =
+                                self.delegate.http_failure(&r, None, None).await
+                            {
+                                tokio::time::sleep(d).await;
+                                continue;
+                            }
+                            return Err(Ok(r));
+                        }
+                    };
+                    return Ok(h.0.last);
+                }
+                Err(err) => {
+                    if let Retry::After(d) = self.delegate.http_error(&err).await {
+                        tokio::time::sleep(d).await;
+                        continue;
+                    }
+                    return Err(Err(err));
+                }
+            }
+        }
+    }
+
+    /// returns None if operation was cancelled by delegate, or the HttpResult.
+    /// It can be that we return the result just because we didn't understand the status code -
+    /// caller should check for status himself before assuming it's OK to use
+    pub async fn upload(&mut self) -> Option<hyper::Result<hyper::Response<hyper::body::Body>>> {
+        let mut start = match self.start_at {
+            Some(s) => s,
+            None => match self.query_transfer_status().await {
+                Ok(s) => s,
+                Err(result) => return Some(result),
+            },
+        };
+
+        const MIN_CHUNK_SIZE: u64 = 1 << 18;
+        let chunk_size = match self.delegate.chunk_size() {
+            cs if cs > MIN_CHUNK_SIZE => cs,
+            _ => MIN_CHUNK_SIZE,
+        };
+
+        self.reader.seek(SeekFrom::Start(start)).unwrap();
+        loop {
+            let request_size = match self.content_length - start {
+                rs if rs > chunk_size => chunk_size,
+                rs => rs,
+            };
+
+            let mut section_reader = self.reader.take(request_size);
+            let mut req_bytes = vec![];
+            section_reader.read_to_end(&mut req_bytes).unwrap();
+            let range_header = ContentRange {
+                range: Some(Chunk {
+                    first: start,
+                    last: start + request_size - 1,
+                }),
+                total_length: self.content_length,
+            };
+            start += request_size;
+            if self.delegate.cancel_chunk_upload(&range_header).await {
+                return None;
+            }
+            let res = self
+                .client
+                .request(
+                    hyper::Request::builder()
+                        .method(hyper::Method::PUT)
+                        .uri(self.url)
+                        .header("Content-Range", range_header.header_value())
+                        .header(CONTENT_TYPE, format!("{}", self.media_type))
+                        .header(USER_AGENT, self.user_agent.to_string())
+                        .header(AUTHORIZATION, self.auth_header.clone())
+                        .body(hyper::body::Body::from(req_bytes))
+                        .unwrap(),
+                )
+                .await;
+            match res {
+                Ok(res) => {
+                    if res.status() == StatusCode::PERMANENT_REDIRECT {
+                        continue;
+                    }
+
+                    let (res_parts, res_body) = res.into_parts();
+                    let res_body_string: String = String::from_utf8(
+                        hyper::body::to_bytes(res_body)
+                            .await
+                            .unwrap()
+                            .into_iter()
+                            .collect(),
+                    )
+                    .unwrap();
+                    let reconstructed_result =
+                        hyper::Response::from_parts(res_parts, res_body_string.clone().into());
+
+                    if !reconstructed_result.status().is_success() {
+                        if let Retry::After(d) = self
+                            .delegate
+                            .http_failure(
+                                &reconstructed_result,
+                                json::from_str(&res_body_string).ok(),
+                                json::from_str(&res_body_string).ok(),
+                            )
+                            .await
+                        {
+                            tokio::time::sleep(d).await;
+                            continue;
+                        }
+                    }
+                    return Some(Ok(reconstructed_result));
+                }
+                Err(err) => {
+                    if let Retry::After(d) = self.delegate.http_error(&err).await {
+                        tokio::time::sleep(d).await;
+                        continue;
+                    }
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+/// The client type used by all hubs, and by the token sources in this module.
+pub type HttpsClient = hyper::Client<HttpsConnector<HttpConnector>, hyper::body::Body>;
+
+/// Returns a new client suitable for talking to google APIs, using the system's root certificates.
+pub fn https_client() -> HttpsClient {
+    hyper::Client::builder().build(HttpsConnector::with_native_roots())
+}
+
+/// The value of the `fields` parameter, which makes the server respond with the selected fields only.
+///
+/// It is built by the typed field selectors in the `fields` module of each API, which only know the fields
+/// that actually exist.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FieldSelector(Vec<String>);
+
+impl FieldSelector {
+    /// Select the field with the given name, including all of its sub-fields.
+    pub fn field(mut self, name: &str) -> FieldSelector {
+        self.0.push(name.to_string());
+        self
+    }
+
+    /// Select the given sub-fields of the field with the given name.
+    /// If none are selected, the field is selected as a whole.
+    pub fn nested(mut self, name: &str, sub_fields: FieldSelector) -> FieldSelector {
+        if sub_fields.is_empty() {
+            return self.field(name);
+        }
+        self.0.push(format!("{}({})", name, sub_fields));
+        self
+    }
+
+    /// Returns true if no field is selected.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Display for FieldSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.join(","))
+    }
+}
+
+/// The error returned when parsing or creating a resource name which doesn't match its pattern.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvalidResourceName {
+    /// The name which failed to parse.
+    pub name: String,
+    /// The pattern it doesn't match, like `projects/{project}/topics/{topic}`.
+    pub pattern: &'static str,
+}
+
+impl Display for InvalidResourceName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "'{}' is not a resource name like '{}'",
+            self.name, self.pattern
+        )
+    }
+}
+
+impl error::Error for InvalidResourceName {}
+
+/// Check that `name` matches the given resource name pattern, like `projects/{project}/topics/{topic}`.
+/// Each `{id}` must be a non-empty id without `/`, everything else must match exactly.
+pub fn check_resource_name(
+    name: &str,
+    pattern: &'static str,
+) -> std::result::Result<(), InvalidResourceName> {
+    let mut segments = name.split('/');
+    let matches = pattern.split('/').all(|expected| match segments.next() {
+        Some(segment) if expected.starts_with('{') => !segment.is_empty(),
+        Some(segment) => segment == expected,
+        None => false,
+    }) && segments.next().is_none();
+    if matches {
+        Ok(())
+    } else {
+        Err(InvalidResourceName {
+            name: name.to_string(),
+            pattern,
+        })
+    }
+}
+
+/// Expand the given [RFC 6570](https://tools.ietf.org/html/rfc6570) URI template up to level 4, taking the
+/// values of its variables from `params`. Variables which occur more than once in `params` are lists.
+///
+/// Values are percent-encoded as required by the expression, so `{name}` encodes a `/` in the value,
+/// whereas the reserved expansion `{+name}` keeps it. Variables without a value are left out.
+pub fn expand_uri_template(template: &str, params: &[(&str, String)]) -> String {
+    let mut res = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        res.push_str(&rest[..start]);
+        expand_uri_template_expression(&rest[start + 1..end], params, &mut res);
+        rest = &rest[end + 1..];
+    }
+    res.push_str(rest);
+    res
+}
+
+fn expand_uri_template_expression(expression: &str, params: &[(&str, String)], out: &mut String) {
+    // (first, separator, named, if-empty, allow-reserved) as of RFC 6570, Appendix A
+    let (variables, (first, sep, named, if_empty, allow_reserved)) = match expression.chars().next()
+    {
+        Some('+') => (&expression[1..], ("", ",", false, "", true)),
+        Some('#') => (&expression[1..], ("#", ",", false, "", true)),
+        Some('.') => (&expression[1..], (".", ".", false, "", false)),
+        Some('/') => (&expression[1..], ("/", "/", false, "", false)),
+        Some(';') => (&expression[1..], (";", ";", true, "", false)),
+        Some('?') => (&expression[1..], ("?", "&", true, "=", false)),
+        Some('&') => (&expression[1..], ("&", "&", true, "=", false)),
+        _ => (expression, ("", ",", false, "", false)),
+    };
+
+    let mut is_first = true;
+    for spec in variables.split(',') {
+        let (name, explode, max_len) = match spec.find(':') {
+            Some(pos) => (&spec[..pos], false, spec[pos + 1..].parse().ok()),
+            None if spec.ends_with('*') => (&spec[..spec.len() - 1], true, None),
+            None => (spec, false, None),
+        };
+        let values: Vec<&str> = params
+            .iter()
+            .filter(|p| p.0 == name)
+            .map(|p| p.1.as_str())
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+
+        out.push_str(if is_first { first } else { sep });
+        is_first = false;
+        let push_named = |out: &mut String, value: &str| {
+            if named {
+                out.push_str(name);
+                out.push_str(if value.is_empty() { if_empty } else { "=" });
+            }
+        };
+        if values.len() == 1 {
+            let value = match max_len {
+                Some(max_len) => match values[0].char_indices().nth(max_len) {
+                    Some((pos, _)) => &values[0][..pos],
+                    None => values[0],
+                },
+                None => values[0],
+            };
+            push_named(out, value);
+            percent_encode_uri_value(value, allow_reserved, out);
+        } else if explode {
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(sep);
+                }
+                push_named(out, value);
+                percent_encode_uri_value(value, allow_reserved, out);
+            }
+        } else {
+            if named {
+                out.push_str(name);
+                out.push('=');
+            }
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                percent_encode_uri_value(value, allow_reserved, out);
+            }
+        }
+    }
+}
+
+fn percent_encode_uri_value(value: &str, allow_reserved: bool, out: &mut String) {
+    const RESERVED: &[u8] = b":/?#[]@!$&'()*+,;=";
+    let bytes = value.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        let is_unreserved = b.is_ascii_alphanumeric() || b"-._~".contains(&b);
+        let is_pct_encoded = b == b'%'
+            && bytes.len() > i + 2
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit();
+        if is_unreserved || (allow_reserved && (RESERVED.contains(&b) || is_pct_encoded)) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+}
+
+// Based on @erickt user comment. Thanks for the idea !
+// Remove all keys whose values are null from given value (changed in place)
+pub fn remove_json_null_values(value: &mut json::value::Value) {
+    match *value {
+        json::value::Value::Object(ref mut map) => {
+            let mut for_removal = Vec::new();
+
+            for (key, value) in map.iter_mut() {
+                if value.is_null() {
+                    for_removal.push(key.clone());
+                } else {
+                    remove_json_null_values(value);
+                }
+            }
+
+            for key in &for_removal {
+                map.remove(key);
+            }
+        }
+        json::value::Value::Array(ref mut arr) => {
+            let mut i = 0;
+            while i < arr.len() {
+                if arr[i].is_null() {
+                    arr.remove(i);
+                } else {
+                    remove_json_null_values(&mut arr[i]);
+                    i += 1;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Set the fields at the given paths to null, which is how fields are cleared in *patch* requests.
+/// Paths are the dot-separated json names of the fields, like `retentionPolicy` or `labels.team`.
+/// Missing objects on the way are created, paths leading through anything else are ignored.
+///
+/// Use it after `remove_json_null_values()`, which removes all fields that are not set.
+pub fn set_json_null_values<S: AsRef<str>>(value: &mut json::value::Value, paths: &[S]) {
+    for path in paths {
+        let mut current = &mut *value;
+        let mut fields = path.as_ref().split('.').peekable();
+        while let Some(field) = fields.next() {
+            let map = match current {
+                json::value::Value::Object(map) => map,
+                _ => break,
+            };
+            if fields.peek().is_none() {
+                map.insert(field.to_string(), json::value::Value::Null);
+                break;
+            }
+            current = map
+                .entry(field)
+                .or_insert_with(|| json::value::Value::Object(Default::default()));
+        }
+    }
+}
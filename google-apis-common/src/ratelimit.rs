@@ -0,0 +1,194 @@
+//! Pacing calls on the client side, to stay within the quotas of an API.
+//!
+//! A [`RateLimiter`] holds a token bucket per method id, or per quota group of methods. Each request
+//! takes a token before it is sent, and waits until one becomes available if there is none. Whenever
+//! the server answers with a `rateLimitExceeded` or `RATE_LIMIT_EXCEEDED` reason, the rate of the
+//! bucket is halved, and it recovers step by step with each successful call.
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::ServerError;
+
+/// The rate and burst size of a token bucket.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quota {
+    /// The amount of requests per second in the long run.
+    pub per_second: f64,
+    /// The amount of requests which may be sent at once, after a time without any.
+    pub burst: u32,
+}
+
+impl Quota {
+    /// A quota of `requests` per second, with a burst of as many.
+    pub fn per_second(requests: u32) -> Quota {
+        Quota {
+            per_second: requests as f64,
+            burst: requests.max(1),
+        }
+    }
+
+    /// A quota of `requests` per minute, with a burst of as many.
+    pub fn per_minute(requests: u32) -> Quota {
+        Quota {
+            per_second: requests as f64 / 60.0,
+            burst: requests.max(1),
+        }
+    }
+}
+
+/// The bucket of a method id or quota group.
+struct Bucket {
+    quota: Quota,
+    /// The current rate, which is lower than the one of the quota after the server refused calls.
+    per_second: f64,
+    /// The tokens available, negative if calls are waiting for them.
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn new(quota: Quota) -> Bucket {
+        Bucket {
+            quota,
+            per_second: quota.per_second,
+            tokens: quota.burst as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.quota.burst as f64);
+        self.refilled_at = now;
+    }
+}
+
+/// A token-bucket rate limiter, to be set on a hub with `rate_limiter(...)`.
+///
+/// Buckets are created on first use, for methods which have a quota set for their id, for their quota
+/// group or by default. Calls of other methods are not paced. A limiter may be shared by hubs through
+/// an `Arc`, for instance to pace calls against the quota of a project.
+///
+/// ```
+/// # use google_apis_common::{Quota, RateLimiter};
+/// let limiter = RateLimiter::new()
+///     .default_quota(Quota::per_second(10))
+///     .group("youtube.search.*", "search")
+///     .quota("search", Quota::per_minute(100));
+/// ```
+#[derive(Default)]
+pub struct RateLimiter {
+    default_quota: Option<Quota>,
+    quotas: HashMap<String, Quota>,
+    groups: Vec<(String, String)>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// A limiter which doesn't pace any calls, until quotas are set.
+    pub fn new() -> RateLimiter {
+        Default::default()
+    }
+
+    /// Set the quota of each method which has neither a quota of its own, nor one of its group.
+    pub fn default_quota(mut self, quota: Quota) -> RateLimiter {
+        self.default_quota = Some(quota);
+        self
+    }
+
+    /// Set the quota of a method id, like `youtube.videos.list`, or of a quota group.
+    pub fn quota<K: Into<String>>(mut self, key: K, quota: Quota) -> RateLimiter {
+        self.quotas.insert(key.into(), quota);
+        self
+    }
+
+    /// Put methods into a quota group, which shares one bucket. The pattern is a method id, or
+    /// a prefix of method ids ending in `*`, like `gmail.users.messages.*`.
+    /// The first group matching a method id wins.
+    pub fn group<P: Into<String>, G: Into<String>>(mut self, pattern: P, group: G) -> RateLimiter {
+        self.groups.push((pattern.into(), group.into()));
+        self
+    }
+
+    /// The key of the bucket used for the given method id.
+    fn key<'a>(&'a self, method_id: &'a str) -> &'a str {
+        self.groups
+            .iter()
+            .find(|(pattern, _)| match pattern.strip_suffix('*') {
+                Some(prefix) => method_id.starts_with(prefix),
+                None => pattern == method_id,
+            })
+            .map(|(_, group)| group.as_str())
+            .unwrap_or(method_id)
+    }
+
+    /// Take a token for a call of the given method, and return how long to wait before sending it.
+    pub fn reserve(&self, method_id: &str) -> Option<Duration> {
+        let key = self.key(method_id);
+        let quota = self.quotas.get(key).or(self.default_quota.as_ref())?;
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(key.to_string())
+            .or_insert_with(|| Bucket::new(*quota));
+        bucket.refill(Instant::now());
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 || bucket.per_second <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(-bucket.tokens / bucket.per_second))
+    }
+
+    /// Wait until a call of the given method may be sent.
+    pub async fn acquire(&self, method_id: &str) {
+        if let Some(delay) = self.reserve(method_id) {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Tell the limiter about the outcome of a call, with the error of the server if it failed.
+    ///
+    /// If the server refused the call for exceeding a rate limit, the rate of its bucket is halved, and the
+    /// tokens it holds are dropped. Each successful call restores a tenth of the rate of the quota.
+    pub fn record(&self, method_id: &str, error: Option<&ServerError>) {
+        let key = self.key(method_id);
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = match buckets.get_mut(key) {
+            Some(bucket) => bucket,
+            None => return,
+        };
+        match error {
+            Some(err) if is_rate_limit_exceeded(err) => {
+                bucket.refill(Instant::now());
+                bucket.per_second = (bucket.per_second / 2.0).max(bucket.quota.per_second / 64.0);
+                bucket.tokens = bucket.tokens.min(0.0);
+            }
+            Some(_) => {}
+            None => {
+                bucket.per_second = (bucket.per_second + bucket.quota.per_second / 10.0)
+                    .min(bucket.quota.per_second)
+            }
+        }
+    }
+
+    /// The current rate of the bucket used for the given method id, if it was used before.
+    pub fn current_rate(&self, method_id: &str) -> Option<f64> {
+        let key = self.key(method_id);
+        self.buckets
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|bucket| bucket.per_second)
+    }
+}
+
+/// Returns true if one of the reasons of the error says that a rate limit was exceeded, like
+/// `rateLimitExceeded`, `userRateLimitExceeded` or `RATE_LIMIT_EXCEEDED`.
+pub fn is_rate_limit_exceeded(err: &ServerError) -> bool {
+    err.errors.iter().any(|message| {
+        let reason = message.reason.replace('_', "").to_lowercase();
+        reason.ends_with("ratelimitexceeded")
+    })
+}
@@ -0,0 +1,145 @@
+//! Checks of parameters and request values against the constraints the API documents for them,
+//! like patterns, enumerations and numeric bounds.
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use crate::{Error, Result, ValidationError};
+
+/// Implemented by all request values, to check their fields before they are sent.
+pub trait Validate {
+    /// Check this value, located at `path` within the request, and record all violations.
+    fn validate_into(&self, validator: &mut Validator, path: &str);
+
+    /// Check this value, and return all violations as `Error::Validation`.
+    #[allow(clippy::result_large_err)]
+    fn validate(&self) -> Result<()> {
+        let mut validator = Validator::default();
+        self.validate_into(&mut validator, "");
+        validator.finish()
+    }
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate_into(&self, validator: &mut Validator, path: &str) {
+        if let Some(value) = self {
+            value.validate_into(validator, path);
+        }
+    }
+}
+
+impl<T: Validate> Validate for Box<T> {
+    fn validate_into(&self, validator: &mut Validator, path: &str) {
+        (**self).validate_into(validator, path);
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate_into(&self, validator: &mut Validator, path: &str) {
+        for (index, value) in self.iter().enumerate() {
+            value.validate_into(validator, &format!("{}[{}]", path, index));
+        }
+    }
+}
+
+impl<T: Validate> Validate for HashMap<String, T> {
+    fn validate_into(&self, validator: &mut Validator, path: &str) {
+        for (key, value) in self.iter() {
+            value.validate_into(validator, &format!("{}[{}]", path, key));
+        }
+    }
+}
+
+/// Collects the violations found while checking parameters and request values.
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<ValidationError>,
+}
+
+impl Validator {
+    /// Return the path of the field `name` of the value at `path`.
+    pub fn field_path(path: &str, name: &str) -> String {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", path, name)
+        }
+    }
+
+    /// Record a violation of the constraints of `field`.
+    pub fn error(&mut self, field: &str, message: String) {
+        self.errors.push(ValidationError {
+            field: field.to_string(),
+            message,
+        });
+    }
+
+    /// Check that a field which is required by the method is set.
+    pub fn required<T>(&mut self, field: &str, value: &Option<T>) {
+        if value.is_none() {
+            self.error(field, "is required".to_string());
+        }
+    }
+
+    /// Check that a required string isn't empty.
+    pub fn not_empty(&mut self, field: &str, value: &str) {
+        if value.is_empty() {
+            self.error(field, "must not be empty".to_string());
+        }
+    }
+
+    /// Check that `value` matches the regular expression `pattern`.
+    /// Patterns which can't be compiled are ignored.
+    pub fn pattern(&mut self, field: &str, value: &str, pattern: &str) {
+        if let Some(re) = compiled_pattern(pattern) {
+            if !re.is_match(value) {
+                self.error(field, format!("'{}' doesn't match '{}'", value, pattern));
+            }
+        }
+    }
+
+    /// Check that `value` is within the given bounds, both inclusive.
+    pub fn range(&mut self, field: &str, value: f64, minimum: Option<f64>, maximum: Option<f64>) {
+        match (minimum, maximum) {
+            (Some(min), _) if value < min => {
+                self.error(field, format!("{} is less than {}", value, min))
+            }
+            (_, Some(max)) if value > max => {
+                self.error(field, format!("{} is greater than {}", value, max))
+            }
+            _ => {}
+        }
+    }
+
+    /// Check that `value` is one of the given values.
+    pub fn one_of(&mut self, field: &str, value: &str, values: &[&str]) {
+        if !values.contains(&value) {
+            self.error(
+                field,
+                format!("'{}' is not one of {}", value, values.join(", ")),
+            );
+        }
+    }
+
+    /// Return `Error::Validation` with all violations, if there are any.
+    #[allow(clippy::result_large_err)]
+    pub fn finish(self) -> Result<()> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(self.errors))
+        }
+    }
+}
+
+/// Return the regular expression of `pattern`, or None if it can't be compiled.
+/// Each pattern is compiled once, and kept for all later calls.
+pub(crate) fn compiled_pattern(pattern: &str) -> Option<regex::Regex> {
+    static PATTERNS: OnceLock<Mutex<HashMap<String, Option<regex::Regex>>>> = OnceLock::new();
+    PATTERNS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(pattern.to_string())
+        .or_insert_with(|| regex::Regex::new(pattern).ok())
+        .clone()
+}
@@ -1,4 +1,5 @@
-<%! from util import (estr, enclose_in, hash_comment, library_to_crate_name, to_extern_crate_name) %>\
+<%! from util import (estr, enclose_in, hash_comment, library_to_crate_name, to_extern_crate_name, new_context,
+                      resource_feature) %>\
 <%namespace name="util" file="lib/util.mako"/>\
 <%block filter="hash_comment">\
 <%util:gen_info source="${self.uri}" />\
@@ -34,11 +35,39 @@ serde = "^ 1.0"
 serde_json = "^ 1.0"
 serde_derive = "^ 1.0"
 yup-oauth2 = "^ 5.0"
-tokio = "^ 1.0"
+tokio = { version = "^ 1.0", features = ["time"] }
 itertools = "^ 0.10"
 % for dep in cargo.get('dependencies', list()):
 ${dep}
 % endfor
+% if make.depends_on_suffix is None:
+google-apis-common = { path = "../${directories.common}/google-apis-common", version = "${cargo.common_version}" }
+% endif
+<%
+  # the api crate has a feature per resource, which are all enabled by default
+  resource_features = list()
+  if make.depends_on_suffix is None:
+    c = new_context(schemas, resources, context.get('methods'))
+    resource_features = [resource_feature(r) for r in sorted(c.rta_map.keys())]
+    for feature in cargo.get('features', list()):
+      assert feature.split('=')[0].strip() not in resource_features, "Feature '%s' clashes with a resource" % feature
+%>\
+% if cargo.get('features') or resource_features:
+
+[features]
+% if resource_features:
+default = [${', '.join('"%s"' % f for f in resource_features)}]
+% endif
+% for feature in cargo.get('features', list()):
+${feature}
+% endfor
+% if resource_features:
+# resources
+% for feature in resource_features:
+${feature} = []
+% endfor
+% endif
+% endif
 
 <%
   api_name = util.library_name()
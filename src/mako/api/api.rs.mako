@@ -7,13 +7,16 @@
     from util import (new_context, rust_comment, rust_doc_comment, rust_module_doc_comment,
                       rb_type, hub_type, mangle_ident, hub_type_params_s, hub_type_bounds,
                       rb_type_params_s, find_fattest_resource, HUB_TYPE_PARAMETERS, METHODS_RESOURCE,
-                      UNUSED_TYPE_MARKER, schema_markers)
+                      UNUSED_TYPE_MARKER, schema_markers, is_field_selector_schema, indent,
+                      resource_names, resource_feature, resource_module, resources_cfg, cfg_attribute,
+                      SPLIT_START, SPLIT_END)
 
     c = new_context(schemas, resources, context.get('methods'))
     hub_type = hub_type(c.schemas, util.canonical_name())
     ht_params = hub_type_params_s()
 
     default_user_agent = "google-api-rust-client/" + cargo.build_version
+    has_quota_user = 'quotaUser' in (parameters or dict())
 %>\
 use std::collections::HashMap;
 use std::cell::RefCell;
@@ -24,7 +27,7 @@ use serde_json as json;
 use std::io;
 use std::fs;
 use std::mem;
-use std::thread::sleep;
+use std::sync::Arc;
 
 use crate::client;
 
@@ -32,7 +35,12 @@ use crate::client;
 // UTILITIES ###
 // ############
 
-${lib.scope_enum()}
+${lib.scope_enum(c)}
+
+${lib.global_param_enums()}\
+% for rn in sorted(resource_names(c).values(), key=lambda rn: rn.type_name):
+${lib.resource_name(rn)}
+% endfor
 
 
 // ########
@@ -49,11 +57,17 @@ ${lib.scope_enum()}
 ${lib.hub_usage_example(c)}\
 </%block>
 pub struct ${hub_type}${ht_params} {
-    client: RefCell<C>,
-    auth: RefCell<oauth2::authenticator::Authenticator<hyper_rustls::HttpsConnector<hyper::client::connect::HttpConnector>>>,
-    _user_agent: String,
-    _base_url: String,
-    _root_url: String,
+    pub(crate) client: RefCell<C>,
+    pub(crate) auth: RefCell<Box<dyn client::GetToken>>,
+    pub(crate) _user_agent: String,
+    pub(crate) _base_url: String,
+    pub(crate) _root_url: String,
+    pub(crate) _read_only: bool,
+    pub(crate) _rate_limiter: Option<Arc<client::RateLimiter>>,
+    pub(crate) _response_cache: Option<Arc<dyn client::ResponseCache>>,
+% if has_quota_user:
+    pub(crate) _quota_user: Option<String>,
+% endif
 }
 
 impl<'a, ${', '.join(HUB_TYPE_PARAMETERS)}> client::Hub for ${hub_type}${ht_params} {}
@@ -61,21 +75,32 @@ impl<'a, ${', '.join(HUB_TYPE_PARAMETERS)}> client::Hub for ${hub_type}${ht_para
 impl<'a, ${', '.join(HUB_TYPE_PARAMETERS)}> ${hub_type}${ht_params}
     where  ${', '.join(hub_type_bounds())} {
 
-    pub fn new(client: C, authenticator: oauth2::authenticator::Authenticator<hyper_rustls::HttpsConnector<hyper::client::connect::HttpConnector>>) -> ${hub_type}${ht_params} {
+    /// Create a new hub which obtains its tokens from the given authenticator.
+    /// It can be any of the `yup_oauth2` authenticators, or one of the token sources in the `client` module,
+    /// like `client::application_default_credentials()`.
+    pub fn new<A: 'static + client::GetToken>(client: C, authenticator: A) -> ${hub_type}${ht_params} {
+        let mut auth: Box<dyn client::GetToken> = Box::new(authenticator);
+        auth.set_root_url("${rootUrl}");
         ${hub_type} {
             client: RefCell::new(client),
-            auth: RefCell::new(authenticator),
+            auth: RefCell::new(auth),
             _user_agent: "${default_user_agent}".to_string(),
             _base_url: "${baseUrl}".to_string(),
             _root_url: "${rootUrl}".to_string(),
+            _read_only: false,
+            _rate_limiter: None,
+            _response_cache: None,
+            % if has_quota_user:
+            _quota_user: None,
+            % endif
         }
     }
 
-    % for resource in sorted(c.rta_map.keys()):
-    pub fn ${mangle_ident(resource)}(&'a self) -> ${rb_type(resource)}${rb_type_params_s(resource, c)} {
-        ${rb_type(resource)} { hub: &self }
+    /// Returns a clone of the http client, which shares its connection pool, so that calls don't keep the hub's
+    /// client borrowed while they are awaited.
+    pub(crate) fn http_client(&self) -> client::HttpsClient {
+        BorrowMut::<client::HttpsClient>::borrow_mut(&mut *self.client.borrow_mut()).clone()
     }
-    % endfor
 
     /// Set the user-agent header field to use in all requests to the server.
     /// It defaults to `${default_user_agent}`.
@@ -98,8 +123,60 @@ impl<'a, ${', '.join(HUB_TYPE_PARAMETERS)}> ${hub_type}${ht_params}
     ///
     /// Returns the previously set root url.
     pub fn root_url(&mut self, new_root_url: String) -> String {
+        self.auth.get_mut().set_root_url(&new_root_url);
         mem::replace(&mut self._root_url, new_root_url)
     }
+
+    /// Set the hub into read-only mode, in which it only authorizes calls with read-only scopes, and refuses
+    /// to call methods which may change data, or which no read-only scope authorizes, with `Error::ReadOnly`.
+    /// It is off by default.
+    ///
+    /// Returns the previous mode.
+    pub fn read_only(&mut self, read_only: bool) -> bool {
+        mem::replace(&mut self._read_only, read_only)
+    }
+
+    /// Set a rate limiter, which paces all calls of this hub before they are sent, and slows down whenever
+    /// the server says a rate limit was exceeded. It may be shared with other hubs. There is none by default.
+    ///
+    /// Returns the previously set rate limiter.
+    pub fn rate_limiter(&mut self, new_rate_limiter: Option<Arc<client::RateLimiter>>) -> Option<Arc<client::RateLimiter>> {
+        mem::replace(&mut self._rate_limiter, new_rate_limiter)
+    }
+
+    /// Set a cache for the responses of calls which read resources. Calls of the same url with the same scopes then
+    /// ask the server to only send the resource if its `ETag` changed, and return the cached value if it didn't,
+    /// along with the server's response with status 304. There is none by default.
+    ///
+    /// Returns the previously set response cache.
+    pub fn response_cache(&mut self, new_response_cache: Option<Arc<dyn client::ResponseCache>>) -> Option<Arc<dyn client::ResponseCache>> {
+        mem::replace(&mut self._response_cache, new_response_cache)
+    }
+    % if has_quota_user:
+
+    /// Set the *quotaUser* to use in all requests to the server, unless a method builder sets its own.
+    /// It identifies the user on whose behalf requests are made, to enforce per-user quotas.
+    ///
+    /// Returns the previously set quota user.
+    pub fn quota_user(&mut self, new_quota_user: Option<String>) -> Option<String> {
+        mem::replace(&mut self._quota_user, new_quota_user)
+    }
+    % endif
+}
+
+#[cfg(feature = "blocking")]
+impl ${hub_type}<client::HttpsClient> {
+    /// Create a new hub for code which isn't async, with a client using the system's root certificates.
+    ///
+    /// The authenticator is created by running the given future on the internal runtime of the `*_blocking()` methods,
+    /// like the `build()` of a `yup_oauth2` authenticator builder, or `client::application_default_credentials()`.
+    /// It must not be called from within an async context.
+    pub fn new_blocking<A, F>(authenticator: F) -> io::Result<${hub_type}<client::HttpsClient>>
+        where A: 'static + client::GetToken,
+              F: std::future::Future<Output = io::Result<A>> {
+        let authenticator = client::block_on(authenticator)?;
+        Ok(${hub_type}::new(client::https_client(), authenticator))
+    }
 }
 
 
@@ -112,26 +189,65 @@ impl<'a, ${', '.join(HUB_TYPE_PARAMETERS)}> ${hub_type}${ht_params}
 ${schema.new(s, c)}
 % endif
 % endfor
-% endif
 
-// ###################
-// MethodBuilders ###
-// #################
+// ####################
+// FIELD SELECTORS ###
+// ##################
 
-% for resource in c.rta_map:
-${rbuild.new(resource, c)}
+/// Typed selectors for the `fields` parameter of all methods, to receive partial responses.
+pub mod fields {
+    use crate::client;
 
+% for s in c.schemas.values():
+% if is_field_selector_schema(s, c):
+${capture(schema.field_selector, s, c) | indent}
+% endif
+% endfor
+}
+% endif
+
+// ##############
+// RESOURCES ###
+// ############
 
+% for resource in sorted(c.rta_map.keys()):
+${cfg_attribute(resources_cfg([resource]))}\
+pub use crate::${resource_module(resource)}::*;
 % endfor
+% for resource in sorted(c.rta_map.keys()):
+${SPLIT_START} ${resource_module(resource)}.rs
+// DO NOT EDIT !
+// This file was generated automatically from '${self.uri}'
+// DO NOT EDIT !
+
+//! All builders of the *${resource}* resource, available with the `${resource_feature(resource)}` feature.
+
+use std::collections::HashMap;
+use std::cell::RefCell;
+use std::borrow::BorrowMut;
+use std::default::Default;
+use std::collections::BTreeMap;
+use serde_json as json;
+use std::io;
+use std::fs;
+use std::mem;
+use std::sync::Arc;
 
+use crate::client;
+use crate::api::*;
 
-// ###################
-// CallBuilders   ###
-// #################
+impl<'a, ${', '.join(HUB_TYPE_PARAMETERS)}> ${hub_type}${ht_params}
+    where  ${', '.join(hub_type_bounds())} {
+    pub fn ${mangle_ident(resource)}(&'a self) -> ${rb_type(resource)}${rb_type_params_s(resource, c)} {
+        ${rb_type(resource)} { hub: &self }
+    }
+}
+
+${rbuild.new(resource, c)}
 
-% for resource, methods in c.rta_map.items():
-% for method in methods:
+% for method in c.rta_map[resource]:
 ${mbuild.new(resource, method, c)}
 
-% endfor ## method in methods
-% endfor ## resource, methods
+% endfor
+${SPLIT_END}
+% endfor
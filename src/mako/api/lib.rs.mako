@@ -1,7 +1,8 @@
 <%namespace name="lib" file="lib/lib.mako"/>\
 <%namespace name="util" file="../lib/util.mako"/>\
 <%
-    from util import (new_context, rust_comment, rust_module_doc_comment)
+    from util import (new_context, rust_comment, rust_module_doc_comment, resource_module, resources_cfg,
+                      cfg_attribute)
 
     c = new_context(schemas, resources, context.get('methods'))
 %>\
@@ -51,7 +52,11 @@ extern crate mime;
 extern crate url;
 
 pub mod api;
-pub mod client;
+% for resource in sorted(c.rta_map.keys()):
+${cfg_attribute(resources_cfg([resource]))}\
+mod ${resource_module(resource)};
+% endfor
+pub extern crate google_apis_common as client;
 
 // Re-export the hub type and some basic client structs
 pub use api::${hub_type};
@@ -5,9 +5,11 @@
                       PART_MARKER_TRAIT, RESOURCE_MARKER_TRAIT, CALL_BUILDER_MARKERT_TRAIT, 
                       find_fattest_resource, build_all_params, pass_through, parts_from_params,
                       REQUEST_MARKER_TRAIT, RESPONSE_MARKER_TRAIT, supports_scopes, to_api_version,
-                      to_fqan, METHODS_RESOURCE, ADD_PARAM_MEDIA_EXAMPLE, PROTOCOL_TYPE_INFO, enclose_in,
+                      to_fqan, METHODS_RESOURCE, ADD_PARAM_MEDIA_EXAMPLE, NULL_FIELD_FN, PROTOCOL_TYPE_INFO, enclose_in,
                       upload_action_fn, METHODS_BUILDER_MARKER_TRAIT, DELEGATE_TYPE,
-                      to_extern_crate_name, rust_doc_sanitize)  
+                      to_extern_crate_name, rust_doc_sanitize, global_params_with_setter, global_param_enum_type,
+                      global_param_enum_variant, global_param_enum_values, is_read_only_scope, method_scopes,
+                      resource_feature)  
 
     def pretty_name(name):
         return ' '.join(split_camelcase_s(name).split('.'))
@@ -190,6 +192,38 @@ ${'##'} A complete example
 
 ${self.hub_usage_example(c, rust_doc, fr=fr)}\
 
+${'##'} Authentication
+
+The hub obtains its tokens from any ${link('authenticator', 'client::GetToken')}. Besides the authenticators
+of [yup-oauth2](https://docs.rs/yup-oauth2), the following token sources are available:
+
+* ${link('service_account_authenticator(key, subject)', 'client::service_account_authenticator')} uses a service account key,
+  optionally acting on behalf of `subject` through domain-wide delegation
+* ${link('MetadataServerAuthenticator', 'client::MetadataServerAuthenticator')} asks the metadata server of
+  Compute Engine, GKE and other Google Cloud runtimes. Set `GCE_METADATA_HOST` to use another server.
+* ${link('application_default_credentials()', 'client::application_default_credentials')} finds
+  *Application Default Credentials* in the file named by `GOOGLE_APPLICATION_CREDENTIALS`, the file written by
+  `gcloud auth application-default login`, or the metadata server, in that order.
+* ${link('ImpersonatedAuthenticator', 'client::ImpersonatedAuthenticator')} uses the tokens of any of the above
+  to obtain short-lived tokens of another service account, optionally through a chain of delegates.
+* ${link('SelfSignedJwtAuthenticator', 'client::SelfSignedJwtAuthenticator')} signs tokens for the hub's root url
+  with a service account key, without asking the token endpoint, for all APIs which accept them.
+* ${link('IdTokenAuthenticator', 'client::IdTokenAuthenticator')} obtains OpenID Connect ID tokens for a custom audience,
+  e.g. to call Cloud Run services or resources behind Identity-Aware Proxy. Incoming ID tokens can be checked with
+  an ${link('IdTokenVerifier', 'client::IdTokenVerifier')}.
+
+```ignore
+let auth = client::application_default_credentials().await?;
+let hub = ${hub_url}::new(client::https_client(), auth);
+```
+% if supports_scopes(auth):
+
+Each method builder asks for the least privileged scope authorizing its method, unless other scopes are added with
+`add_scope(...)`. The scopes of a method are listed by `Scope::allowed_for(...)`. In *read-only* mode, set with
+`hub.read_only(true)`, the hub only asks for read-only scopes, and refuses to call methods which may change data or
+which no read-only scope authorizes.
+% endif
+
 ${'##'} Handling Errors
 
 All errors produced by the system are provided either as ${link('Result', 'client::Result')} enumeration as return value of
@@ -199,16 +233,24 @@ ${link('Hub Delegate', delegate_url)}, or the ${link('Authenticator Delegate', u
 When delegates handle errors or intermediate values, they may have a chance to instruct the system to retry. This 
 makes the system potentially resilient to all kinds of errors.
 
+Along with its decoded value, each successful call returns a ${link('ResponseMeta', 'client::ResponseMeta')}, which tells
+the status and headers of the response, its request id and `ETag`, and how long the call took.
+
 ${'##'} Uploads and Downloads
-If a method supports downloads, the response body, which is part of the ${link('Result', 'client::Result')}, should be
-read by you to obtain the media.
+If a method supports downloads, the response body, which is available through `response()` of the
+${link('ResponseMeta', 'client::ResponseMeta')} in the ${link('Result', 'client::Result')}, holds the media.
 If such a method also supports a ${link('Response Result', 'client::ResponseResult')}, it will return that by default.
 You can see it as meta-data for the actual media. To trigger a media download, you will have to set up the builder by making
 this call: `${ADD_PARAM_MEDIA_EXAMPLE}`.
+Alternatively, `${api.terms.download_action}(...)` writes the media into a file or buffer using `Range` requests. It resumes
+after errors the delegate retries, and with whatever the file holds already. It may split the download into ranges
+which are fetched in parallel, and verifies the media against a ${link('Checksum', 'client::Checksum')} if one is given.
 
 Methods supporting uploads can do so using up to ${len(PROTOCOL_TYPE_INFO)} different protocols: 
 ${put_and(md_italic(PROTOCOL_TYPE_INFO.keys()))}. The distinctiveness of each is represented by customized 
 `${api.terms.action}(...)` methods, which are then named ${put_and(enclose_in('`', ("%s(...)" % upload_action_fn(api.terms.upload_action, v['suffix']) for v in PROTOCOL_TYPE_INFO.values())))} respectively.
+Before anything is sent, the media is checked against the maximum size and the accepted media types of the method,
+which fails with `Error::UploadSizeLimitExceeded` or `Error::UploadMimeTypeNotAccepted` respectively.
 
 ${'##'} Customization and Callbacks
 
@@ -218,6 +260,30 @@ Respective methods will be called to provide progress information, as well as de
 retry on failure.
 
 The ${link('delegate trait', delegate_url)} is default-implemented, allowing you to customize it with minimal effort.
+Its hooks are asynchronous, and each ${link('synchronous delegate', 'client::Delegate')} implements it as well. Delegates
+can be combined, like `RetryDelegate::default().and(my_delegate)`, in which case both are told about the progress of
+a call, and the first one to answer decides.
+
+Method builders can be cloned, and `${api.terms.action}_ref()` performs a call without consuming its builder, to make the
+same call again, like when polling a resource. As the delegate set with `delegate()` is borrowed, it is not part of
+a clone, and `${api.terms.action}_ref()` fails with `Error::BorrowedDelegate` if there is one. A delegate set with
+`shared_delegate(...)` is shared by all clones instead.
+
+The global parameters of the API, like *quotaUser* or *prettyPrint*, have typed setters on every method builder.
+Parameters with a fixed set of values take an enum, like `.alt(Alt::Media)`. A *quotaUser* for all calls can be
+set on the hub instead.
+
+To stay within the quotas of the API, calls can be paced with a ${link('RateLimiter', 'client::RateLimiter')} set on
+the hub, which holds a token bucket per method or group of methods, and slows down whenever the server reports that
+a rate limit was exceeded.
+
+Builders can make calls conditional on the `ETag` of a resource with `if_none_match(...)` and `if_match(...)`, which
+fail with `Error::NotModified` and `Error::PreconditionFailed` respectively if the condition isn't met. A
+${link('ResponseCache', 'client::ResponseCache')} set on the hub, like the `client::MemoryCache`, does so for all calls
+reading resources, and answers them with the cached value if the resource didn't change.
+
+To send a request by other means, `build_request()` prepares it just like `${api.terms.action}()` would, but without
+authorization, and `parse_response()` decodes its response into the same result.
 
 ${'##'} Optional Parts in Server-Requests
 
@@ -227,17 +293,47 @@ are valid.
 Most optionals are are considered ${link('Parts', part_trait_url)} which are identifiable by name, which will be sent to 
 the server to indicate either the set parts of the request or the desired parts in the response.
 
+Optionals which are `None` are not sent at all. To clear a field instead, e.g. in a *patch* request, name it
+using the `${NULL_FIELD_FN}(...)` method of the ${link('method builder', call_builder_url)}, which sends it as `null`.
+
+Responses can be limited to the fields you need with the `fields(...)` method of the
+${link('method builder', call_builder_url)}. It takes a closure which selects the fields through the typed
+selectors in the `api::fields` module. These only know the fields that exist, which makes a misspelled field a
+compile error instead of a response with all fields.
+
 ${'##'} Builder Arguments
 
 Using ${link('method builders', call_builder_url)}, you are able to prepare an action call by repeatedly calling it's methods.
 These will always take a single argument, for which the following statements are true.
 
 * [PODs][wiki-pod] are handed by copy
-* strings are passed as `&str`
+* strings are passed as `&str`, which includes references to the typed resource names, like `projects/{project}`,
+  that can be created from their ids or parsed from a string
 * ${link('request values', request_trait_url)} are moved
 
 Arguments will always be copied or cloned into the builder, to make them independent of their original life times.
 
+${'##'} Cargo Features
+
+% if c.rta_map:
+Each resource has a feature of its own, ${put_and(enclose_in('`', sorted(resource_feature(r) for r in c.rta_map.keys())))}, which
+enables its builders along with all the types they use. All of them are enabled by default. To compile only what you
+use, disable the default features and enable the resources you need, e.g.
+`default-features = false, features = ["${resource_feature(sorted(c.rta_map.keys())[0])}"]`.
+
+% endif
+* `unknown-fields` - all structures which are sent to the server keep the fields they don't know about in their
+  `unknown_fields` map, and send them back as they are. That way, a *get*-*modify*-*update* round-trip doesn't
+  reset fields the API gained after this crate was generated.
+* `validation` - every call builder gets a `validate()` method, which checks the parameters and the request value
+  against what the API documents, like required fields, patterns, enumerations and numeric bounds. Fields set by the
+  server aren't checked, and neither are the enumerations of request values, which may come from a newer server. It is called by
+  `${api.terms.action}()` as well, which fails with `Error::Validation` instead of sending a request the server would reject.
+* `blocking` - every `${api.terms.action}()` and upload method gets a `*_blocking()` variant, like `${api.terms.action}_blocking()`,
+  for code which isn't async. They run on an internal runtime, on which `${hub_url}::new_blocking(...)` creates the
+  authenticator of a new hub as well, like `${hub_url}::new_blocking(client::application_default_credentials())`.
+  Other futures can be run on it with `client::block_on(...)`. None of them may be used from within an async context.
+
 [wiki-pod]: http://en.wikipedia.org/wiki/Plain_old_data_structure
 [builder-pattern]: http://en.wikipedia.org/wiki/Builder_pattern
 [google-go-api]: https://github.com/google/google-api-go-client
@@ -331,14 +427,14 @@ You can read the full text at the repository's [license file][repo-license].
 ## It's possible there is no scope enum if there is no auth information
 ###############################################################################################
 ###############################################################################################
-<%def name="scope_enum()">\
+<%def name="scope_enum(c)">\
 % if not supports_scopes(auth):
 <% return '' %>\
 % endif
 /// Identifies the an OAuth2 authorization scope.
 /// A scope is needed when requesting an
 /// [authorization token](https://developers.google.com/youtube/v3/guides/authentication).
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Scope {
 % for url, scope in auth.oauth2.scopes.items():
     ${scope.description | rust_doc_sanitize, rust_doc_comment}
@@ -375,4 +471,144 @@ impl Default for Scope {
         ${scope_url_to_variant(name, default_url)}
     }
 }
+<%
+    read_only_urls = [url for url in auth.oauth2.scopes.keys() if is_read_only_scope(url)]
+    method_scope_urls = sorted((m.id, [url for url in method_scopes(m) if url in auth.oauth2.scopes])
+                               for m in c.fqan_map.values() if m.get('scopes'))
+%>\
+
+impl Scope {
+    /// Returns true if the scope only grants read access.
+    pub fn is_read_only(&self) -> bool {
+        % if read_only_urls:
+        matches!(*self, ${' | '.join(scope_url_to_variant(name, url) for url in read_only_urls)})
+        % else:
+        false
+        % endif
+    }
+
+    /// Returns the scopes authorizing the method with the given id, like `${method_scope_urls and method_scope_urls[0][0] or 'api.resource.method'}`,
+    /// the least privileged one first.
+    ///
+    /// Unknown methods, and methods which don't need a scope, have none.
+    pub fn allowed_for(method: &str) -> &'static [Scope] {
+        match method {
+            % for mid, urls in method_scope_urls:
+            "${mid}" => &[${', '.join(scope_url_to_variant(name, url) for url in urls)}],
+            % endfor
+            _ => &[],
+        }
+    }
+
+    /// Returns the least privileged scope authorizing the method with the given id, which is also the one
+    /// its method builder uses by default.
+    pub fn minimal_for(method: &str) -> Option<Scope> {
+        Scope::allowed_for(method).first().copied()
+    }
+}
+</%def>
+
+## Builds an enum for each global parameter with a fixed set of values
+###############################################################################################
+###############################################################################################
+<%def name="global_param_enums()">\
+% for pn, p in global_params_with_setter(parameters):
+% if p.get('enum'):
+<% et = global_param_enum_type(schemas, pn) %>\
+/// The possible values of the *${pn}* parameter, which can be set on all method builders.
+///
+${p.get('description', 'no description provided') | rust_doc_sanitize, rust_doc_comment}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ${et} {
+% for v, description in global_param_enum_values(pn, p):
+    ${description | rust_doc_sanitize, rust_doc_comment}
+    ${global_param_enum_variant(v)},
+    % if not loop.last:
+
+    % endif
+% endfor
+}
+
+impl AsRef<str> for ${et} {
+    fn as_ref(&self) -> &str {
+        match *self {
+            % for v, _ in global_param_enum_values(pn, p):
+            ${et}::${global_param_enum_variant(v)} => "${v}",
+            % endfor
+        }
+    }
+}
+
+impl Default for ${et} {
+    fn default() -> ${et} {
+        ${et}::${global_param_enum_variant(p.get('default', p.enum[0]))}
+    }
+}
+
+% endif
+% endfor
+</%def>
+
+## Builds the type of a resource name, like `projects/{project}/topics/{topic}`
+###############################################################################################
+###############################################################################################
+<%def name="resource_name(rn)">\
+<%
+    tn = rn.type_name
+    format_string = '/'.join(cn + '/{}' for cn in rn.collections)
+%>\
+/// The name of a resource, like `${rn.template}`.
+///
+/// It can be passed to all methods taking such a name, as it dereferences to `str`,
+/// and can be parsed from the names returned by the server.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ${tn}(String);
+
+impl ${tn} {
+    /// Create the name from the ids of its parts, which must neither be empty nor contain a `/`.
+    pub fn new(${', '.join('%s: &str' % id for id in rn.ids)}) -> Result<${tn}, client::InvalidResourceName> {
+        format!("${format_string}", ${', '.join(rn.ids)}).parse()
+    }
+% for id in rn.ids:
+
+    /// The id of the *${id}*.
+    pub fn ${id}(&self) -> &str {
+        self.0.split('/').nth(${loop.index * 2 + 1}).unwrap()
+    }
+% endfor
+
+    /// The name as string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::str::FromStr for ${tn} {
+    type Err = client::InvalidResourceName;
+
+    fn from_str(name: &str) -> Result<${tn}, client::InvalidResourceName> {
+        client::check_resource_name(name, "${rn.template}")?;
+        Ok(${tn}(name.to_string()))
+    }
+}
+
+impl std::fmt::Display for ${tn} {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::ops::Deref for ${tn} {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for ${tn} {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
 </%def>
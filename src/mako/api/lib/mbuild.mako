@@ -10,8 +10,11 @@
                       CALL_BUILDER_MARKERT_TRAIT, pass_through, markdown_rust_block, parts_from_params,
                       DELEGATE_PROPERTY_NAME, struct_type_bounds_s, scope_url_to_variant,
                       re_find_replacements, ADD_PARAM_FN, ADD_PARAM_MEDIA_EXAMPLE, upload_action_fn, METHODS_RESOURCE,
-                      method_name_to_variant, size_to_bytes, method_default_scope,
-                      is_repeated_property, setter_fn_name, ADD_SCOPE_FN, rust_doc_sanitize, items)
+                      method_name_to_variant, size_to_bytes, method_default_scope, method_scopes, is_read_only_scope,
+                      is_repeated_property, setter_fn_name, ADD_SCOPE_FN, ADD_SCOPE_URL_FN, NULL_FIELD_FN, rust_doc_sanitize, items,
+                      is_field_selector_schema, global_params_with_setter, global_param_fn_name,
+                      global_param_enum_type, global_param_enum_variant, resource_names,
+                      resource_name_pattern, is_validated_schema, validation_checks, is_mutating_method)
 
     def get_parts(part_prop):
         if not part_prop:
@@ -27,6 +30,25 @@
         part_desc += ''.join('* *%s*\n' % part for part in parts)
         part_desc = part_desc[:-1]
         return part_desc
+
+    # Return [('{foo}', 'foo'), ...], i.e. (find_this, replace_with), for all parameters substituted into the url
+    def url_replacements(m, params):
+        replacements = list()
+        all_required_param_name = set(p.name for p in params if is_required_property(p))
+        for s in re_find_replacements.findall(m.path):
+            if s in [r[0] for r in replacements]: continue
+            # The expansion itself happens at runtime, here we only need the name of the variable,
+            # without operator and modifiers
+            sn = s[1:-1].lstrip('+#./;?&').rstrip('*')
+            assert sn in all_required_param_name, "Expected param '%s' to be in required parameter list for substitution" % sn
+            replacements.append((s, sn))
+        # end for each found substitution
+        # Assure we can substitue everything
+        url = m.path
+        for s, d in replacements:
+            url = url.replace(s, d)
+        assert '{' not in url, "Failed to replace all fields in '%s', have to parse expressions" % url
+        return replacements
 %>\
 <%namespace name="util" file="../../lib/util.mako"/>\
 <%namespace name="lib" file="lib.mako"/>\
@@ -56,6 +78,19 @@
     part_prop, parts = parts_from_params(params)
     part_desc = make_parts_desc(part_prop)
     parts = get_parts(part_prop)
+
+    has_field_selector = response_schema and is_field_selector_schema(response_schema, c) and \
+                         'fields' not in [p.name for p in params]
+
+    # global parameters get their own setter, unless the method has a parameter of the same name already.
+    # 'alt' is set by the method itself, unless it supports media download
+    setter_names = set(mangle_ident(setter_fn_name(p)) for p in params) | \
+                   set((ADD_PARAM_FN, ADD_SCOPE_FN, ADD_SCOPE_URL_FN, NULL_FIELD_FN, 'fields', 'if_none_match', 'if_match',
+                        'shared_delegate'))
+    global_params = [(pn, p) for pn, p in global_params_with_setter(parameters)
+                     if pn not in [p.name for p in params] and global_param_fn_name(pn) not in setter_names and
+                        (pn != 'alt' or m.get('supportsMediaDownload', False))]
+    typed_alt_param = dict(global_params).get('alt')
 %>\
 % if 'description' in m:
 ${m.description | rust_doc_sanitize, rust_doc_comment}
@@ -65,6 +100,8 @@ ${m.description | rust_doc_sanitize, rust_doc_comment}
 /// This method supports **media download**. To enable it, adjust the builder like this:
 % if alt_param:
 /// `.${mangle_ident(setter_fn_name(alt_param))}("media")`.
+% elif typed_alt_param and typed_alt_param.get('enum'):
+/// `.alt(${global_param_enum_type(schemas, 'alt')}::Media)`.
 % else:
 /// `${ADD_PARAM_MEDIA_EXAMPLE}`.
 % endif
@@ -124,6 +161,12 @@ pub struct ${ThisType}
 % endfor
 ## A generic map for additinal parameters. Sometimes you can set some that are documented online only
     ${api.properties.params}: HashMap<String, String>,
+    ${api.properties.if_none_match}: Option<String>,
+    ${api.properties.if_match}: Option<String>,
+    ${api.properties.shared_delegate}: Option<client::SharedDelegate>,
+    % if request_value:
+    ${api.properties.null_fields}: Vec<String>,
+    % endif
     % if method_default_scope(m):
 ## We need the scopes sorted, to not unnecessarily query new tokens
     ${api.properties.scopes}: BTreeMap<String, ()>
@@ -132,6 +175,33 @@ pub struct ${ThisType}
 
 impl${mb_tparams} ${CALL_BUILDER_MARKERT_TRAIT} for ${ThisType} {}
 
+/// A clone has the same parameters and shares the delegate set with `shared_delegate()`, but not the one
+/// set with `delegate()`, which is borrowed by this builder. Thus `${api.terms.action}_ref()` refuses to make calls
+/// without it.
+impl${mb_tparams} Clone for ${ThisType} where ${', '.join(mb_type_bounds())} {
+    fn clone(&self) -> ${ThisType} {
+        ${mb_type(resource, method)} {
+            hub: self.hub,
+            % for p in params:
+            % if p.name == DELEGATE_PROPERTY_NAME:
+            ${property(p.name)}: None,
+            % else:
+            ${property(p.name)}: self.${property(p.name)}.clone(),
+            % endif
+            % endfor
+% for prop_key, custom_name in api.properties.items():
+            % if prop_key == 'scopes' and not method_default_scope(m):
+<% continue %>\
+            % endif
+            % if prop_key == 'null_fields' and not request_value:
+<% continue %>\
+            % endif
+            ${custom_name}: self.${custom_name}.clone(),
+            % endfor
+        }
+    }
+}
+
 impl${mb_tparams} ${ThisType} where ${', '.join(mb_type_bounds())} {
 % if api.get('no_upload_prefix') is not None and ThisType.startswith(api.no_upload_prefix):
 ${self._action_fn(c, resource, method, m, params, request_value, parts, doit_without_upload = True)}\
@@ -139,9 +209,16 @@ ${self._action_fn(c, resource, method, m, params, request_value, parts, doit_wit
 
 ${self._action_fn(c, resource, method, m, params, request_value, parts)}\
 
+${self._build_request_fn(c, m, params, request_value)}\
+
+${self._validate_fn(c, m, params, request_value)}\
+
 ## SETTERS ###############
 % for p in params:
 ${self._setter_fn(resource, method, m, p, part_prop, ThisType, c)}\
+% endfor
+% for pn, p in global_params:
+${self._global_param_setter_fn(pn, p, ThisType)}\
 % endfor
 
     /// Set any additional parameter of the query string used in the request.
@@ -164,6 +241,52 @@ ${self._setter_fn(resource, method, m, p, part_prop, ThisType, c)}\
         self
     }
 
+    /// Only perform the call if the resource doesn't have the given `ETag` anymore, by sending it with
+    /// the `If-None-Match` header. Otherwise, `${api.terms.action}()` fails with `Error::NotModified`.
+    pub fn if_none_match<T>(mut self, etag: T) -> ${ThisType}
+                                                        where T: AsRef<str> {
+        self.${api.properties.if_none_match} = Some(etag.as_ref().to_string());
+        self
+    }
+
+    /// Only perform the call if the resource still has the given `ETag`, by sending it with the `If-Match`
+    /// header. Otherwise, `${api.terms.action}()` fails with `Error::PreconditionFailed`.
+    pub fn if_match<T>(mut self, etag: T) -> ${ThisType}
+                                                        where T: AsRef<str> {
+        self.${api.properties.if_match} = Some(etag.as_ref().to_string());
+        self
+    }
+
+    /// Set a delegate which is shared with the clones of this builder, and locked for the duration of each call.
+    /// A delegate set with `delegate()` takes precedence over it.
+    pub fn shared_delegate(mut self, new_value: client::SharedDelegate) -> ${ThisType} {
+        self.${api.properties.shared_delegate} = Some(new_value);
+        self
+    }
+
+    % if has_field_selector:
+    /// Select the fields of the `${response_schema.id}` the server responds with, instead of all of them.
+    /// This sets the *fields* parameter, and only allows to select fields which actually exist.
+    pub fn fields<F>(mut self, f: F) -> ${ThisType}
+                                                        where F: FnOnce(fields::${response_schema.id}) -> fields::${response_schema.id} {
+        let selector: client::FieldSelector = f(Default::default()).into();
+        self.${api.properties.params}.insert("fields".to_string(), selector.to_string());
+        self
+    }
+
+    % endif
+    % if request_value:
+    /// Send the field at the given path of the request value as `null`, to clear it on the server.
+    ///
+    /// Unset fields are not sent at all, which usually leaves them unchanged. The path uses the json names of
+    /// the fields, separated by dots, like `retentionPolicy` or `labels.team`.
+    pub fn ${NULL_FIELD_FN}<T>(mut self, path: T) -> ${ThisType}
+                                                        where T: AsRef<str> {
+        self.${api.properties.null_fields}.push(path.as_ref().to_string());
+        self
+    }
+    % endif
+
     % if method_default_scope(m):
     /// Identifies the authorization scope for the method you are building.
     ///
@@ -178,21 +301,343 @@ ${self._setter_fn(resource, method, m, p, part_prop, ThisType, c)}\
     ///
     /// Usually there is more than one suitable scope to authorize an operation, some of which may
     /// encompass more rights than others. For example, for listing resources, a *read-only* scope will be
-    /// sufficient, a read-write scope will do as well.
-    pub fn ${ADD_SCOPE_FN}<T, S>(mut self, scope: T) -> ${ThisType}
-                                                        where T: Into<Option<S>>,
-                                                              S: AsRef<str> {
+    /// sufficient, a read-write scope will do as well. All scopes authorizing this method are returned by
+    /// `Scope::allowed_for("${m.id}")`.
+    pub fn ${ADD_SCOPE_FN}<T>(mut self, scope: T) -> ${ThisType}
+                                                        where T: Into<Option<Scope>> {
         match scope.into() {
           Some(scope) => self.${api.properties.scopes}.insert(scope.as_ref().to_string(), ()),
           None => None,
         };
         self
     }
+
+    /// Identifies the authorization scope for the method you are building by its url.
+    ///
+    /// It works like `${ADD_SCOPE_FN}()`, but the url isn't checked at all. Use it for scopes this crate doesn't
+    /// know about, or for scopes which are only known at runtime.
+    pub fn ${ADD_SCOPE_URL_FN}<T>(mut self, scope_url: T) -> ${ThisType}
+                                                        where T: AsRef<str> {
+        self.${api.properties.scopes}.insert(scope_url.as_ref().to_string(), ());
+        self
+    }
     % endif
 }
 </%def>
 
 
+## Collect the query parameters of the call into `params`, to which the url parameters belong as well at first.
+## `delegate_finish` is called on failure, unless it is None.
+###############################################################################################
+###############################################################################################
+<%def name="_query_params(m, params, request_value, reserved_params, delegate_finish)">\
+<%
+    field_params = [p for p in params if p.get('is_query_param', True)]
+    paddfields = 'self.' + api.properties.params
+%>\
+        let mut params: Vec<(&str, String)> = Vec::with_capacity(${len(params) + len(reserved_params)} + ${paddfields}.len());
+        % for p in field_params:
+<%
+    pname = 'self.' + property(p.name)    # property identifier
+%>\
+        ## parts can also be derived from the request, but we do that only if it's not set
+        % if p.name == 'part' and request_value:
+        % if is_repeated_property(p):
+        if ${pname}.is_empty() {
+            params.push(("${p.name}", self.${property(REQUEST_VALUE_PROPERTY_NAME)}.to_parts()));
+        }
+        % elif not is_required_property(p):
+        if ${pname}.is_none() {
+            params.push(("${p.name}", self.${property(REQUEST_VALUE_PROPERTY_NAME)}.to_parts()));
+        }
+        % else:
+        if ${pname}.is_empty() {
+            params.push(("${p.name}", self.${property(REQUEST_VALUE_PROPERTY_NAME)}.to_parts()));
+        } else {
+            params.push(("${p.name}", ${pname}.to_string()));
+        }
+        % endif ## is_repeated_property(p)
+        % endif ## p.name == 'part' and request_value:
+        % if p.get('repeated', False):
+        if ${pname}.len() > 0 {
+            for f in ${pname}.iter() {
+                params.push(("${p.name}", f.to_string()));
+            }
+        }
+        % elif not is_required_property(p):
+        if let Some(ref value) = ${pname} {
+            params.push(("${p.name}", value.to_string()));
+        }
+        % elif not (p.name == 'part' and request_value):
+        params.push(("${p.name}", ${pname}.to_string()));
+        % endif
+        % endfor
+        ## Additional params - may not overlap with optional params
+        for &field in [${', '.join(enclose_in('"', reserved_params + [p.name for p in field_params]))}].iter() {
+            if ${paddfields}.contains_key(field) {
+                % if delegate_finish:
+                ${delegate_finish}(false).await;
+                % endif
+                return Err(client::Error::FieldClash(field));
+            }
+        }
+        for (name, value) in ${paddfields}.iter() {
+            params.push((&name, value.clone()));
+        }
+        % if 'quotaUser' in (parameters or dict()):
+        if let Some(ref quota_user) = self.hub._quota_user {
+            if !params.iter().any(|t| t.0 == "quotaUser") {
+                params.push(("quotaUser", quota_user.clone()));
+            }
+        }
+        % endif
+</%def>
+
+
+## Serialize the request value into `request_value_reader`, of `request_size` bytes and `json_mime_type`
+###############################################################################################
+###############################################################################################
+<%def name="_request_value_reader()">\
+        let mut json_mime_type: mime::Mime = "application/json".parse().unwrap();
+        let mut request_value_reader =
+            {
+                let mut value = json::value::to_value(&self.${property(REQUEST_VALUE_PROPERTY_NAME)}).expect("serde to work");
+                client::remove_json_null_values(&mut value);
+                client::set_json_null_values(&mut value, &self.${api.properties.null_fields});
+                let mut dst = io::Cursor::new(Vec::with_capacity(128));
+                json::to_writer(&mut dst, &value).unwrap();
+                dst
+            };
+        let request_size = request_value_reader.seek(io::SeekFrom::End(0)).unwrap();
+        request_value_reader.seek(io::SeekFrom::Start(0)).unwrap();
+</%def>
+
+
+## In read-only mode, refuse methods which may change data or need a scope which isn't read-only, and narrow the
+## scopes of all others to the read-only ones
+###############################################################################################
+###############################################################################################
+<%def name="_read_only_guard(m, default_scope)">\
+        % if is_mutating_method(m) or (default_scope and not is_read_only_scope(default_scope)):
+        if self.hub._read_only {
+            return Err(client::Error::ReadOnly("${m.id}"));
+        }
+        % elif default_scope:
+        if self.hub._read_only {
+            self.${api.properties.scopes}.retain(|scope, _| ${' || '.join('scope == "%s"' % url for url in method_scopes(m) if is_read_only_scope(url))});
+        }
+        % endif
+</%def>
+
+
+## creates the function building the request without sending it, which `doit()` starts from, along with the one parsing
+## its response
+###############################################################################################
+###############################################################################################
+<%def name="_build_request_fn(c, m, params, request_value)">\
+<%
+    response_schema = method_response(c, m)
+    supports_download = m.get('supportsMediaDownload', False)
+    reserved_params = []
+    rtype = 'client::Result<client::ResponseMeta>'
+    if response_schema:
+        if not supports_download:
+            reserved_params = ['alt']
+        rtype = 'client::Result<(client::ResponseMeta, %s)>' % (response_schema.id)
+    replacements = url_replacements(m, params)
+    media_params = method_media_params(m)
+    # written here, as mako would take `<&` for the start of a tag
+    protocol_type = 'Option<&str>'
+    upload_type_map = dict()
+    if media_params and 'mediaUpload' in m:
+        for mp in media_params:
+            if mp.protocol == 'simple':
+                upload_type_map[mp.protocol] = m.mediaUpload.protocols.simple.multipart and 'multipart' or 'media'
+                break
+        # for each meadia param
+    # end build media param map
+%>\
+    /// Build the request `${api.terms.action}()` would send, without sending it.
+    ///
+    /// It has its final url, query and headers, as well as the serialized request value as body, if there is one.
+    /// Neither authorization nor an API key are part of it, and neither is the media of uploads.
+    /// The response to it can be decoded with `parse_response()`.
+    % if media_params:
+    pub fn build_request(&self) -> client::Result<hyper::Request<hyper::body::Body>> {
+        self.build_upload_request(None)
+    }
+
+    /// Build the request `build_request()` would, but to the endpoint of the given upload protocol, if any.
+    fn build_upload_request(&self, protocol: ${protocol_type}) -> client::Result<hyper::Request<hyper::body::Body>> {
+    % else:
+    pub fn build_request(&self) -> client::Result<hyper::Request<hyper::body::Body>> {
+    % endif
+        use std::io::Seek;
+        use hyper::header::{CONTENT_TYPE, CONTENT_LENGTH, USER_AGENT};
+        use client::ToParts;
+        #[cfg(feature = "validation")]
+        self.validate()?;
+${self._query_params(m, params, request_value, reserved_params, None)}\
+        % if response_schema:
+        % if supports_download:
+        if !params.iter().any(|t| t.0 == "alt") {
+            params.push(("alt", "json".to_string()));
+        }
+        % else:
+        params.push(("alt", "json".to_string()));
+        % endif
+        % endif ## response schema
+
+        % if media_params:
+        let mut url = match protocol {
+            % for mp in media_params:
+            Some("${mp.protocol}") => {
+                params.push(("uploadType", "${upload_type_map.get(mp.protocol, mp.protocol)}".to_string()));
+                self.hub._root_url.clone() + "${mp.path.lstrip('/')}"
+            }
+            % endfor
+            Some(_) => unreachable!(),
+            None => self.hub._base_url.clone() + "${m.path}",
+        };
+        % else:
+        let mut url = self.hub._base_url.clone() + "${m.path}";
+        % endif
+        % if replacements:
+        url = client::expand_uri_template(&url, &params);
+        let used_params = [${', '.join('"%s"' % r[1] for r in replacements)}];
+        params.retain(|t| !used_params.contains(&t.0));
+        % endif
+
+        let url = url::Url::parse_with_params(&url, params).unwrap();
+        let mut req_builder = hyper::Request::builder().method(${method_name_to_variant(m.httpMethod)}).uri(url.into_string())
+                .header(USER_AGENT, self.hub._user_agent.clone());
+        if let Some(ref etag) = self.${api.properties.if_none_match} {
+            req_builder = req_builder.header(hyper::header::IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(ref etag) = self.${api.properties.if_match} {
+            req_builder = req_builder.header(hyper::header::IF_MATCH, etag.clone());
+        }
+        % if request_value:
+${self._request_value_reader()}\
+        let request = req_builder
+                .header(CONTENT_TYPE, format!("{}", json_mime_type))
+                .header(CONTENT_LENGTH, request_size as u64)
+                .body(hyper::body::Body::from(request_value_reader.into_inner()));
+        % else:
+        let request = req_builder.body(hyper::body::Body::empty());
+        % endif
+        Ok(request.unwrap())
+    }
+
+    /// Decode the response to a request made by `build_request()` into the result `${api.terms.action}()` would return.
+    ///
+    /// Responses with a non-success status are returned as error, just like their json error body, if any.
+    % if response_schema and supports_download:
+    /// Responses which are not json, like the media of a download, are not decoded, and a default value is returned instead.
+    % endif
+    pub async fn parse_response(&self, response: hyper::Response<hyper::body::Body>) -> ${rtype} {
+        let (res_parts, res_body) = response.into_parts();
+        let res_body_bytes = match hyper::body::to_bytes(res_body).await {
+            Ok(bytes) => bytes,
+            Err(err) => return Err(client::Error::HttpError(err)),
+        };
+        let res_body_string = String::from_utf8_lossy(&res_body_bytes).into_owned();
+        let reconstructed_result = hyper::Response::from_parts(res_parts, res_body_string.clone().into());
+
+        match reconstructed_result.status() {
+            hyper::StatusCode::NOT_MODIFIED => return Err(client::Error::NotModified(reconstructed_result)),
+            hyper::StatusCode::PRECONDITION_FAILED if self.${api.properties.if_match}.is_some() => {
+                return Err(client::Error::PreconditionFailed(reconstructed_result))
+            }
+            _ => {}
+        }
+        if !reconstructed_result.status().is_success() {
+            return match json::from_str::<client::ErrorResponse>(&res_body_string) {
+                Err(_) => Err(client::Error::Failure(reconstructed_result)),
+                Ok(serr) => Err(client::Error::BadRequest(serr))
+            }
+        }
+        % if response_schema:
+        % if supports_download:
+        let is_json = reconstructed_result.headers().get(hyper::header::CONTENT_TYPE)
+                                          .and_then(|v| v.to_str().ok())
+                                          .map(|v| v.starts_with("application/json"))
+                                          .unwrap_or(false);
+        if !is_json {
+            return Ok((reconstructed_result.into(), Default::default()));
+        }
+        % endif
+        match json::from_str(&res_body_string) {
+            Ok(decoded) => Ok((reconstructed_result.into(), decoded)),
+            Err(err) => Err(client::Error::JsonDecodeError(res_body_string, err)),
+        }
+        % else:
+        Ok(reconstructed_result.into())
+        % endif
+    }
+</%def>
+
+
+## creates the function checking all parameters and the request value of the call builder
+###############################################################################################
+###############################################################################################
+<%def name="_validate_fn(c, m, params, request_value)">\
+<%
+    checks = list()
+    for p in params:
+        if 'location' not in p:
+            continue
+        field = '"%s"' % p.name
+        mn = 'self.' + property(p.name)
+        if is_repeated_property(p):
+            value_checks = validation_checks(p, field, 'value')
+            if value_checks:
+                checks.append(('for value in %s.iter() {' % mn, value_checks))
+        elif is_required_property(p):
+            if activity_rust_type(schemas, p, allow_optionals=False) == 'String':
+                checks.append(('validator.not_empty(%s, &%s);' % (field, mn), None))
+            checks.extend((check, None) for check in validation_checks(p, field, '&' + mn))
+        else:
+            value_checks = validation_checks(p, field, 'value')
+            if value_checks:
+                checks.append(('if let Some(ref value) = %s {' % mn, value_checks))
+    # end for each parameter
+    if request_value and is_validated_schema(request_value, c):
+        rv = 'self.' + property(REQUEST_VALUE_PROPERTY_NAME)
+        checks.append(('client::Validate::validate_into(&%s, &mut validator, "%s");'
+                       % (rv, REQUEST_VALUE_PROPERTY_NAME), None))
+        for pn, p in items(request_value.properties):
+            if m.id in p.get('annotations', dict()).get('required', list()):
+                checks.append(('validator.required("%s.%s", &%s.%s);'
+                               % (REQUEST_VALUE_PROPERTY_NAME, pn, rv, mangle_ident(pn)), None))
+        # end for each required property
+    # end handle request value
+%>\
+    /// Check the parameters and the request value against the constraints of the API, like required fields,
+    /// patterns and bounds, without sending anything.
+    ///
+    /// This is done by `${api.terms.action}()` as well, which fails with `Error::Validation` before making the request.
+    #[cfg(feature = "validation")]
+    pub fn validate(&self) -> client::Result<()> {
+    % if checks:
+        let mut validator = client::Validator::default();
+    % for check, value_checks in checks:
+        ${check}
+        % if value_checks:
+        % for value_check in value_checks:
+            ${value_check}
+        % endfor
+        }
+        % endif
+    % endfor
+        validator.finish()
+    % else:
+        Ok(())
+    % endif
+    }
+</%def>
+
+
 ## creates a setter for the call builder
 ###############################################################################################
 ###############################################################################################
@@ -249,6 +694,10 @@ ${self._setter_fn(resource, method, m, p, part_prop, ThisType, c)}\
     ///
     ${part_desc | rust_doc_sanitize, rust_doc_comment, indent_all_but_first_by(1)}
     % endif
+    % if resource_name_pattern(p) in resource_names(c):
+    ///
+    /// It can also be set from a [`${resource_names(c)[resource_name_pattern(p)].type_name}`], by passing a reference to it.
+    % endif
     pub fn ${mangle_ident(setter_fn_name(p))}(mut self, ${value_name}: ${InType}) -> ${ThisType} {
         % if p.get('repeated', False):
         self.${property(p.name)}.push(${new_value_copied});
@@ -260,6 +709,32 @@ ${self._setter_fn(resource, method, m, p, part_prop, ThisType, c)}\
 </%def>
 
 
+## creates a setter for a global parameter, which is stored with the additional parameters
+###############################################################################################
+###############################################################################################
+<%def name="_global_param_setter_fn(pn, p, ThisType)">\
+<%
+    value = 'new_value.to_string()'
+    if p.get('enum'):
+        InType = global_param_enum_type(schemas, pn)
+        value = 'new_value.as_ref().to_string()'
+    elif p.type == 'boolean':
+        InType = 'bool'
+    else:
+        InType = '&str'
+%>\
+    % if 'description' in p:
+    ${p.description | rust_doc_sanitize, rust_doc_comment, indent_all_but_first_by(1)}
+    % endif
+    ///
+    /// Sets the *${pn}* query property to the given value.
+    pub fn ${global_param_fn_name(pn)}(mut self, new_value: ${InType}) -> ${ThisType} {
+        self.${api.properties.params}.insert("${pn}".to_string(), ${value});
+        self
+    }
+</%def>
+
+
 ## creates usage docs the method builder
 ## show_all: If True, we will show all comments and hide no prelude. It's good to build a complete,
 ## documented example for a given method.
@@ -383,9 +858,17 @@ match result {
         |Error::MissingToken(_)
         |Error::Cancelled
         |Error::UploadSizeLimitExceeded(_, _)
+        |Error::UploadMimeTypeNotAccepted(_, _)
+        |Error::ChecksumMismatch(_, _)
+        |Error::Io(_)
         |Error::Failure(_)
         |Error::BadRequest(_)
         |Error::FieldClash(_)
+        |Error::Validation(_)
+        |Error::ReadOnly(_)
+        |Error::NotModified(_)
+        |Error::PreconditionFailed(_)
+        |Error::BorrowedDelegate
         |Error::JsonDecodeError(_, _) => println!("{}", e),
     },
     Ok(res) => println!("Success: {:?}", res),
@@ -412,7 +895,7 @@ match result {
     where = ''
     qualifier = 'pub '
     add_args = ''
-    rtype = 'client::Result<hyper::Response<hyper::body::Body>>'
+    rtype = 'client::Result<client::ResponseMeta>'
     response_schema = method_response(c, m)
 
     supports_download = m.get('supportsMediaDownload', False);
@@ -420,12 +903,14 @@ match result {
     if response_schema:
         if not supports_download:
             reserved_params = ['alt']
-        rtype = 'client::Result<(hyper::Response<hyper::body::Body>, %s)>' % (response_schema.id)
+        rtype = 'client::Result<(client::ResponseMeta, %s)>' % (response_schema.id)
+    # responses of GET calls are cached by their ETag, if the hub has a cache
+    use_cache = response_schema and not media_params and m.httpMethod == 'GET'
 
     mtype_param = 'RS'
 
-    possible_urls = [m.path]
     simple_media_param = None
+    raw_media_param = None
     resumable_media_param = None
     if media_params:
         type_params = '<%s>' % mtype_param
@@ -435,6 +920,8 @@ match result {
         for p in media_params:
             if p.protocol == 'simple':
                 simple_media_param = p
+            elif p.protocol == 'media':
+                raw_media_param = p
             elif p.protocol == 'resumable':
                 resumable_media_param = p
     # end handle media params
@@ -444,55 +931,16 @@ match result {
     else:
         action_fn = qualifier + 'async fn ' + api.terms.action + type_params + ('(mut self%s)' % add_args) + ' -> ' + rtype + where
 
-    field_params = [p for p in params if p.get('is_query_param', True)]
-
-    paddfields = 'self.' + api.properties.params
 
     delegate = 'self.' + property(DELEGATE_PROPERTY_NAME)
     delegate_finish = 'dlg.finished'
-    auth_call = 'self.hub.auth.borrow_mut()'
+    auth_call = 'self.hub.auth.borrow()'
 
     default_scope = method_default_scope(m)
 
-    # s = '{foo}' -> ('{foo}', 'foo') -> (find_this, replace_with)
-    seen = set()
-    replacements = list()
-    all_required_param_name = set(p.name for p in params if is_required_property(p))
-    MULTI_SLASH = 'multi-slash-prefix'
-    URL_ENCODE = 'url-encode'
+    replacements = url_replacements(m, params)
 
-    READER_SEEK = "let size = reader.seek(io::SeekFrom::End(0)).unwrap();\nreader.seek(io::SeekFrom::Start(0)).unwrap();\n"
-    if media_params:
-        max_size = media_params[0].max_size
-        if max_size > 0:
-            READER_SEEK += "if size > %i {\n\treturn Err(client::Error::UploadSizeLimitExceeded(size, %i))\n}" % (max_size, max_size)
-
-    special_cases = set()
-    for possible_url in possible_urls:
-        for s in re_find_replacements.findall(possible_url):
-            if s in seen: continue
-            seen.add(s)
-            sn = s[1:-1]
-
-            # NOTE: We only handle the cases that are actually used in the schemas. If this shouldn't
-            # be worth it anymore (i.e. too many cases), then we should use a uri-template library
-            # to handle this at runtime, possibly, or use a python uri-template library, to more easily
-            # handle the required cases. Whatever is less work, I guess.
-            if sn.startswith('/') and sn.endswith('*'):
-                sn = sn[1:-1]
-                special_cases.add(MULTI_SLASH)
-            elif sn.startswith('+'):
-                sn = sn[1:]
-                special_cases.add(URL_ENCODE)
-            assert sn in all_required_param_name, "Expected param '%s' to be in required parameter list for substitution" % sn
-            replacements.append((s, sn))
-        # end for each found substitution
-        # Assure we can substitue everything
-        for s, d in replacements:
-            possible_url = possible_url.replace(s, d)
-        assert '{' not in possible_url, "Failed to replace all fields in '%s', have to parse expressions" % possible_url
-    # end for each possible url
-    del seen
+    READER_SEEK = "reader.seek(io::SeekFrom::Start(0)).unwrap();"
 %>
     % if doit_without_upload:
     /// Perform the operation you have build so far, but without uploading. This is used to e.g. renaming or updating the description for a file
@@ -500,119 +948,46 @@ match result {
     /// Perform the operation you have build so far.
     % endif
     ${action_fn} {
-        % if URL_ENCODE in special_cases:
-        use url::percent_encoding::{percent_encode, DEFAULT_ENCODE_SET};
-        % endif
         use std::io::{Read, Seek};
-        use hyper::header::{CONTENT_TYPE, CONTENT_LENGTH, AUTHORIZATION, USER_AGENT, LOCATION};
-        use client::ToParts;
+        use hyper::header::{CONTENT_TYPE, CONTENT_LENGTH, AUTHORIZATION, LOCATION, IF_NONE_MATCH};
+${self._read_only_guard(m, default_scope)}\
+        ## the request sent is the one `build_request()` makes, with authorization, the API key and the media added
+        let request = self.${media_params and 'build_upload_request(Some(protocol))' or 'build_request()'}?;
+        let started_at = std::time::Instant::now();
         let mut dd = client::DefaultDelegate;
-        let mut dlg: &mut dyn client::Delegate = match ${delegate} {
-            Some(d) => d,
-            None => &mut dd
+        let shared_delegate = self.${api.properties.shared_delegate}.clone();
+        let mut shared_dlg;
+        let mut dlg: &mut dyn client::AsyncDelegate = match (${delegate}, shared_delegate.as_ref()) {
+            (Some(d), _) => d,
+            (None, Some(shared)) => {
+                shared_dlg = shared.lock().await;
+                &mut *shared_dlg
+            }
+            (None, None) => &mut dd
         };
         dlg.begin(client::MethodInfo { id: "${m.id}",
-                               http_method: ${method_name_to_variant(m.httpMethod)} });
-        let mut params: Vec<(&str, String)> = Vec::with_capacity(${len(params) + len(reserved_params)} + ${paddfields}.len());
-<%
-    if media_params and 'mediaUpload' in m:
-        upload_type_map = dict()
-        for mp in media_params:
-            if mp.protocol == 'simple':
-                upload_type_map[mp.protocol] = m.mediaUpload.protocols.simple.multipart and 'multipart' or 'media'
-                break
-        # for each meadia param
-    # end build media param map
-%>\
-        % for p in field_params:
-<%
-    pname = 'self.' + property(p.name)    # property identifier
-%>\
-        ## parts can also be derived from the request, but we do that only if it's not set
-        % if p.name == 'part' and request_value:
-        % if is_repeated_property(p):
-        if ${pname}.is_empty() {
-            ${pname}.push(self.${property(REQUEST_VALUE_PROPERTY_NAME)}.to_parts());
-        }
-        % else:
-        % if not is_required_property(p):
-        if ${pname}.is_none() {
-            ${pname} = Some(self.${property(REQUEST_VALUE_PROPERTY_NAME)}.to_parts());
-        }
-        % else:
-        if ${pname}.len() == 0 {
-            ${pname} = self.${property(REQUEST_VALUE_PROPERTY_NAME)}.to_parts();
-        }
-        % endif ## not is_required_property(p)
-        % endif is_repeated_property(p):
-        % endif ## p.name == 'part' and request_value:
-        % if p.get('repeated', False):
-        if ${pname}.len() > 0 {
-            for f in ${pname}.iter() {
-                params.push(("${p.name}", f.to_string()));
-            }
-        }
-        % elif not is_required_property(p):
-        if let Some(value) = ${pname} {
-            params.push(("${p.name}", value.to_string()));
-        }
-        % else:
-        params.push(("${p.name}", ${pname}.to_string()));
-        % endif
-        % endfor
-        ## Additional params - may not overlap with optional params
-        for &field in [${', '.join(enclose_in('"', reserved_params + [p.name for p in field_params]))}].iter() {
-            if ${paddfields}.contains_key(field) {
-                ${delegate_finish}(false);
-                return Err(client::Error::FieldClash(field));
-            }
-        }
-        for (name, value) in ${paddfields}.iter() {
-            params.push((&name, value.clone()));
-        }
+                               http_method: ${method_name_to_variant(m.httpMethod)} }).await;
+        % if media_params:
 
-        % if response_schema:
-        % if supports_download:
-        let (json_field_missing, enable_resource_parsing) = {
-            let mut enable = true;
-            let mut field_present = true;
-            for &(name, ref value) in params.iter() {
-                if name == "alt" {
-                    field_present = false;
-                    if <String as AsRef<str>>::as_ref(&value) != "json" {
-                        enable = false;
-                    }
-                    break;
-                }
-            }
-            (field_present, enable)
-        };
-        if json_field_missing {
-            params.push(("alt", "json".to_string()));
+        let size = reader.seek(io::SeekFrom::End(0)).unwrap();
+        reader.seek(io::SeekFrom::Start(0)).unwrap();
+        if let Err(err) = client::check_upload(size, ${media_params[0].max_size}, &reader_mime_type, &[${', '.join('"%s"' % a for a in media_params[0].accept)}]) {
+            ${delegate_finish}(false).await;
+            return Err(err);
         }
-        % else:
-        params.push(("alt", "json".to_string()));
-        % endif ## supportsMediaDownload
-        % endif ## response schema
+        % endif
 
-        % if media_params:
-        let (mut url, upload_type) =
-            % for mp in media_params:
-            % if loop.first:
-            if \
-            % else:
-else if \
-            % endif
-protocol == "${mp.protocol}" {
-                (self.hub._root_url.clone() + "${mp.path.lstrip('/')}", "${upload_type_map.get(mp.protocol, mp.protocol)}")
-            } \
-            % endfor
-else {
-                unreachable!()
-            };
-        params.push(("uploadType", upload_type.to_string()));
-        % else:
-        let mut url = self.hub._base_url.clone() + "${m.path}";
+        let (parts, ${request_value and 'body' or '_'}) = request.into_parts();
+        let mut url = url::Url::parse(&parts.uri.to_string()).unwrap();
+        % if request_value:
+        let request_value_bytes = hyper::body::to_bytes(body).await.unwrap();
+        % if simple_media_param:
+        let json_mime_type: mime::Mime = "application/json".parse().unwrap();
+        let request_size = request_value_bytes.len() as u64;
+        % endif
+        % endif
+        % if response_schema and supports_download:
+        let enable_resource_parsing = self.${api.properties.params}.get("alt").map_or(true, |alt| alt == "json");
         % endif
         % if not default_scope:
         % if no_auth is UNDEFINED:
@@ -621,12 +996,14 @@ else {
         %>
         let mut key = ${auth_call}.api_key();
         if key.is_none() {
-            key = dlg.api_key();
+            key = dlg.api_key().await;
         }
         match key {
-            Some(value) => params.push(("key", value)),
+            Some(value) => {
+                url.query_pairs_mut().append_pair("key", &value);
+            }
             None => {
-                ${delegate_finish}(false);
+                ${delegate_finish}(false).await;
                 return Err(client::Error::MissingAPIKey)
             }
         }
@@ -637,63 +1014,18 @@ else {
         }
         % endif
 
-        ## Hanlde URI Tempates
-        % if replacements:
-        for &(find_this, param_name) in [${', '.join('("%s", "%s")' % r for r in replacements)}].iter() {
-<%
-    replace_init = ': Option<&str> = None'
-    replace_assign = 'Some(value)'
-    url_replace_arg = 'replace_with.expect("to find substitution value in params")'
-    if URL_ENCODE in special_cases:
-        replace_init = ' = String::new()'
-        replace_assign = 'value.to_string()'
-        url_replace_arg = '&replace_with'
-    # end handle url encoding
-%>\
-            let mut replace_with${replace_init};
-            for &(name, ref value) in params.iter() {
-                if name == param_name {
-                    replace_with = ${replace_assign};
-                    break;
-                }
-            }
-            % if URL_ENCODE in special_cases:
-            if find_this.as_bytes()[1] == '+' as u8 {
-                replace_with = percent_encode(replace_with.as_bytes(), DEFAULT_ENCODE_SET).to_string();
-            }
-            % endif
-            url = url.replace(find_this, ${url_replace_arg});
-        }
-        ## Remove all used parameters
-        {
-            let mut indices_for_removal: Vec<usize> = Vec::with_capacity(${len(replacements)});
-            for param_name in [${', '.join(reversed(['"%s"' % r[1] for r in replacements]))}].iter() {
-                if let Some(index) = params.iter().position(|t| &t.0 == param_name) {
-                    indices_for_removal.push(index);
-                }
-            }
-            for &index in indices_for_removal.iter() {
-                params.remove(index);
-            }
-        }
-        % endif
-
-        let url = url::Url::parse_with_params(&url, params).unwrap();
-
-        % if request_value:
-        let mut json_mime_type: mime::Mime = "application/json".parse().unwrap();
-        let mut request_value_reader =
-            {
-                let mut value = json::value::to_value(&self.${property(REQUEST_VALUE_PROPERTY_NAME)}).expect("serde to work");
-                client::remove_json_null_values(&mut value);
-                let mut dst = io::Cursor::new(Vec::with_capacity(128));
-                json::to_writer(&mut dst, &value).unwrap();
-                dst
-            };
-        let request_size = request_value_reader.seek(io::SeekFrom::End(0)).unwrap();
-        request_value_reader.seek(io::SeekFrom::Start(0)).unwrap();
+        % if use_cache:
+        let cache_key = client::cache_key(url.as_str(), \
+% if default_scope:
+self.${api.properties.scopes}.keys());
+% else:
+std::iter::empty());
+% endif
+        let mut cached = match self.hub._response_cache {
+            Some(ref cache) if self.${api.properties.if_none_match}.is_none()${supports_download and ' && enable_resource_parsing' or ''} => cache.get(&cache_key),
+            _ => None,
+        };
         % endif
-
         % if resumable_media_param:
         let mut should_ask_dlg_for_url = false;
         let mut upload_url_from_server;
@@ -701,27 +1033,26 @@ else {
         % endif
 
         loop {
+            if let Some(ref limiter) = self.hub._rate_limiter {
+                limiter.acquire("${m.id}").await;
+            }
             % if default_scope:
-            let authenticator = ${auth_call};
-            let token = match authenticator.token(&self.${api.properties.scopes}.keys().collect::<Vec<_>>()[..]).await {
+            let token = match ${auth_call}.token(&self.${api.properties.scopes}.keys().map(String::as_str).collect::<Vec<_>>()[..]).await {
                 Ok(token) => token.clone(),
                 Err(err) => {
-                    match  dlg.token(&err) {
+                    match dlg.token(&err).await {
                         Some(token) => token,
                         None => {
-                            ${delegate_finish}(false);
+                            ${delegate_finish}(false).await;
                             return Err(client::Error::MissingToken(err))
                         }
                     }
                 }
             };
             % endif
-            % if request_value:
-            request_value_reader.seek(io::SeekFrom::Start(0)).unwrap();
-            % endif
             let mut req_result = {
             % if resumable_media_param:
-                if should_ask_dlg_for_url && (upload_url = dlg.upload_url()) == () && upload_url.is_some() {
+                if should_ask_dlg_for_url && (upload_url = dlg.upload_url().await) == () && upload_url.is_some() {
                     should_ask_dlg_for_url = false;
                     upload_url_from_server = false;
                     let url = upload_url.as_ref().and_then(|s| Some(url::Url::parse(s).unwrap())).unwrap();
@@ -733,28 +1064,18 @@ else {
                 } else {
             % endif
 <%block filter="indent_by(resumable_media_param and 4 or 0)">\
-            % if request_value and simple_media_param:
-                let mut mp_reader: client::MultiPartReader = Default::default();
-                let (mut body_reader, content_type) = match protocol {
-                    "${simple_media_param.protocol}" => {
-                        mp_reader.reserve_exact(2);
-                        ${READER_SEEK | indent_all_but_first_by(5)}
-                        mp_reader.add_part(&mut request_value_reader, request_size, json_mime_type.clone())
-                                 .add_part(&mut reader, size, reader_mime_type.clone());
-                        let mime_type = mp_reader.mime_type();
-                        (&mut mp_reader as &mut dyn io::Read, (CONTENT_TYPE, format!("{}", mime_type)))
-                    },
-                    _ => (&mut request_value_reader as &mut dyn io::Read, (CONTENT_TYPE, format!("{}", json_mime_type))),
-                };
-            % endif
-                let mut client = &mut *self.hub.client.borrow_mut();
-                dlg.pre_request();
-                let mut req_builder = hyper::Request::builder().method(${method_name_to_variant(m.httpMethod)}).uri(url.clone().into_string())
-                        .header(USER_AGENT, self.hub._user_agent.clone())\
-                        % if default_scope:
-                            .header(AUTHORIZATION, format!("Bearer {}", token.as_str()))\
-                        % endif
-;
+                let client = self.hub.http_client();
+                dlg.pre_request().await;
+                let mut req_builder = hyper::Request::builder().method(parts.method.clone()).uri(url.as_str());
+                *req_builder.headers_mut().unwrap() = parts.headers.clone();
+                % if default_scope:
+                req_builder = req_builder.header(AUTHORIZATION, format!("Bearer {}", token.as_str()));
+                % endif
+                % if use_cache:
+                if let Some(ref entry) = cached {
+                    req_builder = req_builder.header(IF_NONE_MATCH, entry.etag.clone());
+                }
+                % endif
 
                 % if resumable_media_param:
                 upload_url_from_server = true;
@@ -766,23 +1087,48 @@ else {
                 % if request_value:
                     % if not simple_media_param:
                         let request = req_builder
-                        .header(CONTENT_TYPE, format!("{}", json_mime_type))
-                        .header(CONTENT_LENGTH, request_size as u64)
-                        .body(hyper::body::Body::from(request_value_reader.get_ref().clone()))\
+                        .body(hyper::body::Body::from(request_value_bytes.clone()))\
                     % else:
+                        let mut mp_reader: client::MultiPartReader = Default::default();
+                        let mut request_value_reader = io::Cursor::new(&request_value_bytes[..]);
+                        let (mut body_reader, content_type) = match protocol {
+                            "${simple_media_param.protocol}" => {
+                                mp_reader.reserve_exact(2);
+                                ${READER_SEEK}
+                                mp_reader.add_part(&mut request_value_reader, request_size, json_mime_type.clone())
+                                         .add_part(&mut reader, size, reader_mime_type.clone());
+                                let mime_type = mp_reader.mime_type();
+                                (&mut mp_reader as &mut dyn io::Read, format!("{}", mime_type))
+                            },
+                            % if raw_media_param:
+                            "${raw_media_param.protocol}" => {
+                                ${READER_SEEK}
+                                (&mut reader as &mut dyn io::Read, format!("{}", reader_mime_type))
+                            },
+                            % endif
+                            _ => (&mut request_value_reader as &mut dyn io::Read, format!("{}", json_mime_type)),
+                        };
                         let mut body_reader_bytes = vec![];
                         body_reader.read_to_end(&mut body_reader_bytes).unwrap();
+                        let headers = req_builder.headers_mut().unwrap();
+                        headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+                        headers.insert(CONTENT_LENGTH, body_reader_bytes.len().into());
                         let request = req_builder
-                        .header(content_type.0, content_type.1)
                         .body(hyper::body::Body::from(body_reader_bytes))\
                     % endif ## not simple_media_param
                 % else:
                     % if simple_media_param:
-                        let request = if protocol == "${simple_media_param.protocol}" {
-                            ${READER_SEEK | indent_all_but_first_by(4)}
-                            req_builder.header(CONTENT_TYPE, reader_mime_type.clone())
+                        let request = if protocol == "${simple_media_param.protocol}"\
+                        % if raw_media_param:
+ || protocol == "${raw_media_param.protocol}"\
+                        % endif
+ {
+                            ${READER_SEEK}
+                            let mut media_bytes = Vec::with_capacity(size as usize);
+                            reader.read_to_end(&mut media_bytes).unwrap();
+                            req_builder.header(CONTENT_TYPE, format!("{}", reader_mime_type))
                                      .header(CONTENT_LENGTH, size)
-                                     .body(&mut reader);
+                                     .body(hyper::body::Body::from(media_bytes))
                         } else {
                             req_builder.body(hyper::body::Body::empty())
                         }\
@@ -793,7 +1139,7 @@ else {
                 % endif
 ;
 
-                client.borrow_mut().request(request.unwrap()).await
+                client.request(request.unwrap()).await
                 
 </%block>\
                 % if resumable_media_param:
@@ -803,16 +1149,16 @@ else {
 
             match req_result {
                 Err(err) => {
-                    if let client::Retry::After(d) = dlg.http_error(&err) {
-                        sleep(d);
+                    if let client::Retry::After(d) = dlg.http_error(&err).await {
+                        tokio::time::sleep(d).await;
                         continue;
                     }
-                    ${delegate_finish}(false);
+                    ${delegate_finish}(false).await;
                     return Err(client::Error::HttpError(err))
                 }
-                Ok(mut res) => {
+                Ok(res) => {
                     let (res_parts, res_body) = res.into_parts();
-                    let res_body_string: String = String::from_utf8(
+                    let mut res_body_string: String = String::from_utf8(
                         hyper::body::to_bytes(res_body)
                             .await
                             .unwrap()
@@ -820,22 +1166,39 @@ else {
                             .collect(),
                     )
                     .unwrap();
-                    let reconstructed_result =
+                    let mut reconstructed_result =
                         hyper::Response::from_parts(res_parts, res_body_string.clone().into());
 
+                    if reconstructed_result.status() == hyper::StatusCode::NOT_MODIFIED {
+                        % if use_cache:
+                        if let Some((entry, decoded)) = cached.take().and_then(|entry| json::from_str(&entry.body).ok().map(|decoded| (entry, decoded))) {
+                            ${delegate_finish}(true).await;
+                            return Ok((client::ResponseMeta::from_cache(reconstructed_result, &entry, started_at.elapsed()), decoded));
+                        }
+                        % endif
+                        ${delegate_finish}(false).await;
+                        return Err(client::Error::NotModified(reconstructed_result));
+                    }
+                    if reconstructed_result.status() == hyper::StatusCode::PRECONDITION_FAILED && self.${api.properties.if_match}.is_some() {
+                        ${delegate_finish}(false).await;
+                        return Err(client::Error::PreconditionFailed(reconstructed_result));
+                    }
                     if !reconstructed_result.status().is_success() {
                         let json_server_error = json::from_str::<client::JsonServerError>(&res_body_string).ok();
                         let server_error = json::from_str::<client::ServerError>(&res_body_string)
                             .or_else(|_| json::from_str::<client::ErrorResponse>(&res_body_string).map(|r| r.error))
                             .ok();
+                        if let (Some(limiter), Some(err)) = (self.hub._rate_limiter.as_ref(), server_error.as_ref()) {
+                            limiter.record("${m.id}", Some(err));
+                        }
 
                         if let client::Retry::After(d) = dlg.http_failure(&reconstructed_result,
                                                               json_server_error,
-                                                              server_error) {
-                            sleep(d);
+                                                              server_error).await {
+                            tokio::time::sleep(d).await;
                             continue;
                         }
-                        ${delegate_finish}(false);
+                        ${delegate_finish}(false).await;
                         return match json::from_str::<client::ErrorResponse>(&res_body_string){
                             Err(_) => Err(client::Error::Failure(reconstructed_result)),
                             Ok(serr) => Err(client::Error::BadRequest(serr))
@@ -843,19 +1206,18 @@ else {
                     }
                     % if resumable_media_param:
                     if protocol == "${resumable_media_param.protocol}" {
-                        ${READER_SEEK | indent_all_but_first_by(6)}
-                        let mut client = &mut *self.hub.client.borrow_mut();
+                        ${READER_SEEK}
+                        let mut client = self.hub.http_client();
                         let upload_result = {
                             let url_str = &reconstructed_result.headers().get("Location").expect("LOCATION header is part of protocol").to_str().unwrap();
                             if upload_url_from_server {
-                                dlg.store_upload_url(Some(url_str));
+                                dlg.store_upload_url(Some(url_str)).await;
                             }
 
                             client::ResumableUploadHelper {
-                                client: &mut client.borrow_mut(),
+                                client: &mut client,
                                 delegate: dlg,
                                 start_at: if upload_url_from_server { Some(0) } else { None },
-                                auth: &mut *self.hub.auth.borrow_mut(),
                                 user_agent: &self.hub._user_agent,
                                 auth_header: format!("Bearer {}", token.as_str()),
                                 url: url_str,
@@ -866,28 +1228,40 @@ else {
                         };
                         match upload_result {
                             None => {
-                                ${delegate_finish}(false);
+                                ${delegate_finish}(false).await;
                                 return Err(client::Error::Cancelled)
                             }
                             Some(Err(err)) => {
                                 ## Do not ask the delgate again, as it was asked by the helper !
-                                ${delegate_finish}(false);
+                                ${delegate_finish}(false).await;
                                 return Err(client::Error::HttpError(err))
                             }
                             ## Now the result contains the actual resource, if any ... it will be
                             ## decoded next
-                            Some(Ok(upload_result)) => {
-                                res = upload_result;
+                            Some(Ok(res)) => {
                                 if !res.status().is_success() {
                                     ## delegate was called in upload() already - don't tell him again
-                                    dlg.store_upload_url(None);
-                                    ${delegate_finish}(false);
+                                    dlg.store_upload_url(None).await;
+                                    ${delegate_finish}(false).await;
                                     return Err(client::Error::Failure(res))
                                 }
+                                ## the response to the last chunk describes the call, not the one starting the session
+                                let (res_parts, res_body) = res.into_parts();
+                                res_body_string = String::from_utf8(
+                                    hyper::body::to_bytes(res_body)
+                                        .await
+                                        .unwrap()
+                                        .into_iter()
+                                        .collect(),
+                                )
+                                .unwrap();
+                                reconstructed_result =
+                                    hyper::Response::from_parts(res_parts, res_body_string.clone().into());
                             }
                         }
                     }
                     % endif
+                    let response_meta = client::ResponseMeta::new(reconstructed_result, started_at.elapsed());
                 % if response_schema:
                     ## If 'alt' is not json, we cannot attempt to decode the response
                     let result_value = \
@@ -896,37 +1270,137 @@ if enable_resource_parsing \
                     % endif
 {
                         match json::from_str(&res_body_string) {
-                            Ok(decoded) => (reconstructed_result, decoded),
+                            Ok(decoded) => (response_meta, decoded),
                             Err(err) => {
-                                dlg.response_json_decode_error(&res_body_string, &err);
+                                dlg.response_json_decode_error(&res_body_string, &err).await;
                                 return Err(client::Error::JsonDecodeError(res_body_string, err));
                             }
                         }
                     }\
                     % if supports_download:
- else { (reconstructed_result, Default::default()) }\
+ else { (response_meta, Default::default()) }\
                     % endif
 ;
                 % else:
-                    let result_value = reconstructed_result;
+                    let result_value = response_meta;
                 % endif
 
-                    ${delegate_finish}(true);
+                    % if use_cache:
+                    if let Some(ref cache) = self.hub._response_cache {
+                        let etag = result_value.0.etag();
+                        if let Some(etag) = etag${supports_download and '.filter(|_| enable_resource_parsing)' or ''} {
+                            cache.put(cache_key, client::CachedResponse { etag: etag.to_string(), body: res_body_string });
+                        }
+                    }
+                    % endif
+                    if let Some(ref limiter) = self.hub._rate_limiter {
+                        limiter.record("${m.id}", None);
+                    }
+                    ${delegate_finish}(true).await;
                     return Ok(result_value)
                 }
             }
         }
     }
 
+    % if not media_params:
+<% action_name = doit_without_upload and 'doit_without_upload' or api.terms.action %>\
+
+    /// Like `${action_name}()`, but blocks the current thread until the operation is done.
+    ///
+    /// It runs on an internal runtime, and must not be called from within an async context.
+    #[cfg(feature = "blocking")]
+    pub fn ${action_name}_blocking(self) -> ${rtype} {
+        client::block_on(self.${action_name}())
+    }
+
+    /// Like `${action_name}()`, but performs the call with a clone of this builder, which can be used again,
+    /// for instance to poll a resource. As the delegate set with `delegate()` can't be part of the clone, it fails
+    /// with `Error::BorrowedDelegate` if there is one, see `shared_delegate()` instead.
+    pub async fn ${action_name}_ref(&self) -> ${rtype} {
+        if ${delegate}.is_some() {
+            return Err(client::Error::BorrowedDelegate);
+        }
+        self.clone().${action_name}().await
+    }
+    % endif
+    % if supports_download and default_scope and not media_params and not doit_without_upload:
+
+    /// Download the media of this call into `writer` with `Range` requests, and return its size in bytes.
+    ///
+    /// Whatever `writer` holds already is taken as the beginning of the media, which allows to resume an interrupted
+    /// download into a file. If `checksum` is set, the media must match it.
+    /// The delegate determines the size of each chunk and the amount of ranges downloaded in parallel, and is told
+    /// about each chunk before it is downloaded, see `client::DownloadHelper`. Each chunk obtains its token from the
+    /// hub's authenticator, so that downloads outlasting a token continue with a fresh one.
+    pub async fn ${api.terms.download_action}<W>(mut self, writer: &mut W, checksum: Option<client::Checksum>) -> client::Result<u64>
+                where W: io::Read + io::Write + io::Seek {
+${self._read_only_guard(m, default_scope)}\
+        self.${api.properties.params}.insert("alt".to_string(), "media".to_string());
+        if self.${api.properties.scopes}.len() == 0 {
+            self.${api.properties.scopes}.insert(${scope_url_to_variant(name, default_scope, fully_qualified=True)}.as_ref().to_string(), ());
+        }
+        let url = self.build_request()?.uri().to_string();
+        let mut dd = client::DefaultDelegate;
+        let shared_delegate = self.${api.properties.shared_delegate}.clone();
+        let mut shared_dlg;
+        let dlg: &mut dyn client::AsyncDelegate = match (${delegate}, shared_delegate.as_ref()) {
+            (Some(d), _) => d,
+            (None, Some(shared)) => {
+                shared_dlg = shared.lock().await;
+                &mut *shared_dlg
+            }
+            (None, None) => &mut dd
+        };
+        dlg.begin(client::MethodInfo { id: "${m.id}",
+                               http_method: ${method_name_to_variant(m.httpMethod)} }).await;
+        if let Some(ref limiter) = self.hub._rate_limiter {
+            limiter.acquire("${m.id}").await;
+        }
+        let scopes = self.${api.properties.scopes}.keys().map(String::as_str).collect::<Vec<_>>();
+        let auth = ${auth_call};
+        let client = self.hub.http_client();
+        let result = client::DownloadHelper {
+            client: &client,
+            delegate: &mut *dlg,
+            user_agent: &self.hub._user_agent,
+            auth: Some(&**auth),
+            scopes: &scopes,
+            url: &url,
+            checksum,
+        }.download(writer).await;
+        ${delegate_finish}(result.is_ok()).await;
+        result
+    }
+
+    /// Like `${api.terms.download_action}()`, but blocks the current thread until the download is done.
+    ///
+    /// It runs on an internal runtime, and must not be called from within an async context.
+    #[cfg(feature = "blocking")]
+    pub fn ${api.terms.download_action}_blocking<W>(self, writer: &mut W, checksum: Option<client::Checksum>) -> client::Result<u64>
+                where W: io::Read + io::Write + io::Seek {
+        client::block_on(self.${api.terms.download_action}(writer, checksum))
+    }
+    % endif
     % for p in media_params:
+<% upload_fn = upload_action_fn(api.terms.upload_action, p.type.suffix) %>\
     ${p.description | rust_doc_sanitize, rust_doc_comment, indent_all_but_first_by(1)}
     ///
     % for item_name, item in p.info.items():
     /// * *${split_camelcase_s(item_name)}*: ${isinstance(item, (list, tuple)) and put_and(enclose_in("'", item)) or str(item)}
     % endfor
-    pub async fn ${upload_action_fn(api.terms.upload_action, p.type.suffix)}<${mtype_param}>(self, ${p.type.arg_name}: ${mtype_param}, mime_type: mime::Mime) -> ${rtype}
+    pub async fn ${upload_fn}<${mtype_param}>(self, ${p.type.arg_name}: ${mtype_param}, mime_type: mime::Mime) -> ${rtype}
                 where ${mtype_param}: client::ReadSeek {
         self.${api.terms.action}(${p.type.arg_name}, mime_type, "${p.protocol}").await
     }
+
+    /// Like `${upload_fn}()`, but blocks the current thread until the upload is done.
+    ///
+    /// It runs on an internal runtime, and must not be called from within an async context.
+    #[cfg(feature = "blocking")]
+    pub fn ${upload_fn}_blocking<${mtype_param}>(self, ${p.type.arg_name}: ${mtype_param}, mime_type: mime::Mime) -> ${rtype}
+                where ${mtype_param}: client::ReadSeek {
+        client::block_on(self.${upload_fn}(${p.type.arg_name}, mime_type))
+    }
     % endfor
 </%def>
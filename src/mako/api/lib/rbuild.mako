@@ -70,7 +70,7 @@ impl${rb_params} ${ThisType} {
     mb_tparams = mb_type_params_s(m)
     # we would could have information about data requirements for each property in it's dict.
     # for now, we just hardcode it, and treat the entries as way to easily change param names
-    assert len(api.properties) == 2, "Hardcoded for now, thanks to scope requirements"
+    assert len(api.properties) == 6, "Hardcoded for now, thanks to scope and request value requirements"
 
     type_params = ''
     if mb_additional_type_params(m):
@@ -121,6 +121,9 @@ impl${rb_params} ${ThisType} {
             % endfor
 % for prop_key, custom_name in api.properties.items():
             % if prop_key == 'scopes' and not method_default_scope(m):
+<% continue %>\
+            % endif
+            % if prop_key == 'null_fields' and not request_value:
 <% continue %>\
             % endif
             ${custom_name}: Default::default(),
@@ -3,12 +3,14 @@
                       IO_TYPES, activity_split, enclose_in, REQUEST_MARKER_TRAIT, mb_type, indent_all_but_first_by,
                       NESTED_TYPE_SUFFIX, RESPONSE_MARKER_TRAIT, split_camelcase_s, METHODS_RESOURCE,
                       PART_MARKER_TRAIT, canonical_type_name, TO_PARTS_MARKER, UNUSED_TYPE_MARKER, is_schema_with_optionals,
-                      rust_doc_sanitize, items)
+                      rust_doc_sanitize, items, field_selector_schema_id, is_validated_schema,
+                      validated_schema_id, validation_checks, is_output_only_property, schema_resources,
+                      resources_cfg, cfg_attribute)
 %>\
 ## Build a schema which must be an object
 ###################################################################################################################
 ###################################################################################################################
-<%def name="_new_object(s, properties, c, allow_optionals)">\
+<%def name="_new_object(s, properties, c, allow_optionals, keeps_unknown_fields, cfg)">\
 <% struct = 'pub struct ' + s.id %>\
 % if properties:
 ${struct} {
@@ -19,6 +21,13 @@ ${struct} {
     % endif
     pub ${mangle_ident(pn)}: ${to_rust_type(schemas, s.id, pn, p, allow_optionals=allow_optionals)},
 % endfor
+% if keeps_unknown_fields:
+    /// All fields the server sent which are unknown to this version of the API. They are sent back as they are,
+    /// which makes sure an update doesn't reset fields added to the API after this crate was generated.
+    #[cfg(feature = "unknown-fields")]
+    #[serde(flatten)]
+    pub unknown_fields: json::Map<String, json::Value>,
+% endif
 }
 % elif 'additionalProperties' in s:
 ${struct}(${to_rust_type(schemas, s.id, NESTED_TYPE_SUFFIX, s, allow_optionals=allow_optionals)});
@@ -37,6 +46,7 @@ pub enum ${et} {
 % endfor
 }
 
+${cfg}\
 impl Default for ${et} {
     fn default() -> ${et} {
         ${et}::${variant_type(s.variant.map[0])}(Default::default())
@@ -66,29 +76,36 @@ ${struct} { _never_set: Option<bool> }
     
     nt_markers = schema_markers(s, c, transitive=False)
     allow_optionals = is_schema_with_optionals(nt_markers)
+    # Only types which are sent to the server need to hold on to what they don't understand
+    keeps_unknown_fields = REQUEST_MARKER_TRAIT in markers
     
     # waiting for Default: https://github.com/rust-lang/rustc-serialize/issues/71
     if s.type == 'any':
         traits.remove('Default')
 
     s_type = s.id
+    # the schema is only needed by the resources using it
+    resources_predicate = resources_cfg(schema_resources(s, c))
+    cfg = cfg_attribute(resources_predicate)
 %>\
 <%block filter="rust_doc_sanitize, rust_doc_comment">\
 ${doc(s, c)}\
 </%block>
+${cfg}\
 #[derive(${', '.join(traits)})]
 % if s.type == 'object':
-${_new_object(s, s.get('properties'), c, allow_optionals)}\
+${_new_object(s, s.get('properties'), c, allow_optionals, keeps_unknown_fields, cfg)}\
 % elif s.type == 'array':
 % if s.items.get('type') != 'object':
 pub struct ${s_type}(${to_rust_type(schemas, s.id, NESTED_TYPE_SUFFIX, s, allow_optionals=allow_optionals)});
 % else:
-${_new_object(s, s.items.get('properties'), c, allow_optionals)}\
+${_new_object(s, s.items.get('properties'), c, allow_optionals, keeps_unknown_fields, cfg)}\
 % endif ## array item != 'object'
 % elif s.type == 'any':
 ## waiting for Default: https://github.com/rust-lang/rustc-serialize/issues/71
 pub struct ${s_type}(json::Value);
 
+${cfg}\
 impl Default for ${s_type} {
     fn default() -> ${s_type} {
         ${s_type}(json::Value::Null)
@@ -100,11 +117,13 @@ impl Default for ${s_type} {
 
 % for marker_trait in nt_markers:
 % if marker_trait not in (TO_PARTS_MARKER, UNUSED_TYPE_MARKER):
+${cfg}\
 impl ${marker_trait} for ${s_type} {}
 % endif
 % endfor
 
 % if TO_PARTS_MARKER in nt_markers and allow_optionals:
+${cfg}\
 impl ${TO_PARTS_MARKER} for ${s_type} {
     /// Return a comma separated list of members that are currently set, i.e. for which `self.member.is_some()`.
     /// The produced string is suitable for use as a parts list that indicates the parts you are sending, and/or
@@ -127,6 +146,52 @@ impl ${TO_PARTS_MARKER} for ${s_type} {
     }
 }
 % endif
+% if is_validated_schema(s, c):
+
+${_validate(s, c, resources_predicate)}\
+% endif
+</%def>
+
+## Implement client::Validate for a schema which is sent to the server
+###################################################################################################################
+###################################################################################################################
+<%def name="_validate(s, c, resources_predicate)">\
+<%
+    checks = list()
+    for pn, p in items(s.properties):
+        ## values set by the server come back from a get, and are sent as they are on update
+        if is_output_only_property(p):
+            continue
+        field = '&client::Validator::field_path(path, "%s")' % pn
+        mn = 'self.' + mangle_ident(pn)
+        if validated_schema_id(schemas, c, s.id, pn, p):
+            checks.append(('client::Validate::validate_into(&%s, validator, %s);' % (mn, field), None))
+            continue
+        ## the server may know enum values this crate doesn't, which would fail a get-modify-update round-trip
+        value_checks = validation_checks(p, field, 'value', check_enum=False)
+        if not value_checks:
+            continue
+        if p.get('type') == 'array':
+            checks.append(('for value in %s.iter().flatten() {' % mn, value_checks))
+        else:
+            checks.append(('if let Some(ref value) = %s {' % mn, value_checks))
+    # end for each property
+    args = checks and 'validator: &mut client::Validator, path: &str' or '_validator: &mut client::Validator, _path: &str'
+%>\
+${cfg_attribute('feature = "validation"', resources_predicate)}\
+impl client::Validate for ${s.id} {
+    fn validate_into(&self, ${args}) {
+    % for check, value_checks in checks:
+        ${check}
+        % if value_checks:
+        % for value_check in value_checks:
+            ${value_check}
+        % endfor
+        }
+        % endif
+    % endfor
+    }
+}
 </%def>
 
 #########################################################################################################
@@ -162,3 +227,43 @@ This type is not used in any activity, and only used as *part* of another schema
 The contained type is `${to_rust_type(schemas, s.id, s.id, s)}`.
 %endif
 </%def>
+
+## Create the field selector of a schema, which lives in the `fields` module
+###################################################################################################################
+###################################################################################################################
+<%def name="field_selector(s, c)">\
+<% cfg = cfg_attribute(resources_cfg(schema_resources(s, c))) %>\
+/// Selects the fields of a [`${s.id}`](super::${s.id}) that are part of a partial response.
+${cfg}\
+#[derive(Clone, Debug, Default)]
+pub struct ${s.id}(client::FieldSelector);
+
+${cfg}\
+impl ${s.id} {
+% for pn, p in items(s.properties):
+<% sid = field_selector_schema_id(schemas, c, s.id, pn, p) %>\
+    % if sid:
+    /// Select the *${pn}* field, limited to the sub-fields chosen by `f`, if any.
+    pub fn ${mangle_ident(pn)}<F>(self, f: F) -> ${s.id}
+        where F: FnOnce(${sid}) -> ${sid} {
+        ${s.id}(self.0.nested("${pn}", f(Default::default()).0))
+    }
+    % else:
+    /// Select the *${pn}* field.
+    pub fn ${mangle_ident(pn)}(self) -> ${s.id} {
+        ${s.id}(self.0.field("${pn}"))
+    }
+    % endif
+% if not loop.last:
+
+% endif
+% endfor
+}
+
+${cfg}\
+impl From<${s.id}> for client::FieldSelector {
+    fn from(selector: ${s.id}) -> client::FieldSelector {
+        selector.0
+    }
+}
+</%def>
@@ -1,6 +1,7 @@
 <%
     from util import (markdown_comment, new_context)
-    from cli import (CONFIG_DIR, CONFIG_DIR_FLAG, SCOPE_FLAG, application_secret_path, DEBUG_FLAG, DEBUG_AUTH_FLAG)
+    from cli import (CONFIG_DIR, CONFIG_DIR_FLAG, SCOPE_FLAG, application_secret_path, DEBUG_FLAG, DEBUG_AUTH_FLAG,
+                     AUTH_FLAG, SERVICE_ACCOUNT_KEY_FLAG, SUBJECT_FLAG, IMPERSONATE_FLAG)
 
     c = new_context(schemas, resources, context.get('methods'))
 %>\
@@ -64,6 +65,19 @@ is necessary.
 
 To revoke granted authentication, please refer to the [official documentation][revoke-access].
 
+Instead of asking a user, the CLI can obtain its tokens in other ways using the `--${AUTH_FLAG}` flag:
+
+* `service-account` uses the service account key file given with `--${SERVICE_ACCOUNT_KEY_FLAG}`, which implies this flow.
+  Use `--${SUBJECT_FLAG}` to act on behalf of a user of a domain that granted domain-wide delegation to the service account.
+* `metadata` asks the metadata server of the Google Cloud machine the CLI runs on, like a Compute Engine VM or a GKE pod.
+  The `GCE_METADATA_HOST` environment variable overrides the server's address.
+* `application-default` finds *Application Default Credentials* like all Google Cloud client libraries do, by trying
+  the file named by `GOOGLE_APPLICATION_CREDENTIALS`, the file written by `gcloud auth application-default login`
+  and the metadata server, in that order.
+
+With `--${IMPERSONATE_FLAG}`, the tokens obtained this way are used to act as the given service account instead, which
+requires the *Service Account Token Creator* role on it.
+
 # Application Secrets
 
 In order to allow any application to use Google services, it will need to be registered using the 
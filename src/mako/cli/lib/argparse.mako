@@ -6,7 +6,9 @@
     from cli import (mangle_subcommand, new_method_context, PARAM_FLAG, STRUCT_FLAG, UPLOAD_FLAG, OUTPUT_FLAG, VALUE_ARG,
                      CONFIG_DIR, SCOPE_FLAG, is_request_value_property, FIELD_SEP, docopt_mode, FILE_ARG, MIME_ARG, OUT_ARG,
                      CONFIG_DIR_FLAG, KEY_VALUE_ARG, to_docopt_arg, DEBUG_FLAG, DEBUG_AUTH_FLAG, MODE_ARG, SCOPE_ARG,
-                     CONFIG_DIR_ARG, FILE_FLAG, MIME_FLAG, subcommand_md_filename)
+                     CONFIG_DIR_ARG, FILE_FLAG, MIME_FLAG, subcommand_md_filename, AUTH_FLAG, AUTH_ARG,
+                     SERVICE_ACCOUNT_KEY_FLAG, SERVICE_ACCOUNT_KEY_ARG, SUBJECT_FLAG, SUBJECT_ARG,
+                     IMPERSONATE_FLAG, IMPERSONATE_ARG)
 
     def rust_boolean(v):
         return v and 'true' or 'false'
@@ -71,6 +73,18 @@ Configuration:
             A directory into which we will store our persistent data. Defaults to
             a user-writable directory that we will create during the first invocation.
             [default: ${CONFIG_DIR}]
+  --${AUTH_FLAG} <${AUTH_ARG}>
+            How to obtain access tokens. One of 'installed', 'service-account', 'metadata'
+            or 'application-default'. Defaults to 'installed', which asks for your consent
+            in the browser, unless --${SERVICE_ACCOUNT_KEY_FLAG} is set.
+  --${SERVICE_ACCOUNT_KEY_FLAG} <${SERVICE_ACCOUNT_KEY_ARG}>
+            The JSON key file of the service account to use for the 'service-account' flow.
+  --${SUBJECT_FLAG} <${SUBJECT_ARG}>
+            The user to act on behalf of with the 'service-account' flow. It requires
+            domain-wide delegation to be enabled for the service account.
+  --${IMPERSONATE_FLAG} <${IMPERSONATE_ARG}>
+            The email of a service account to impersonate, using the tokens obtained by
+            the authentication flow, which need the permission to do so.
   --${DEBUG_FLAG}
             Output all server communication to standard error. `tx` and `rx` are placed
             into the same stream.
@@ -112,6 +126,38 @@ Configuration:
         False,
     ))
 
+    global_args.append((
+        AUTH_FLAG,
+        "How to obtain access tokens. One of 'installed', 'service-account', 'metadata' "
+        "or 'application-default'. Defaults to 'installed', which asks for your consent "
+        "in the browser, unless --%s is set." % SERVICE_ACCOUNT_KEY_FLAG,
+        AUTH_ARG,
+        False,
+    ))
+
+    global_args.append((
+        SERVICE_ACCOUNT_KEY_FLAG,
+        "The JSON key file of the service account to use for the 'service-account' flow.",
+        SERVICE_ACCOUNT_KEY_ARG,
+        False,
+    ))
+
+    global_args.append((
+        SUBJECT_FLAG,
+        "The user to act on behalf of with the 'service-account' flow. It requires "
+        "domain-wide delegation to be enabled for the service account.",
+        SUBJECT_ARG,
+        False,
+    ))
+
+    global_args.append((
+        IMPERSONATE_FLAG,
+        "The email of a service account to impersonate, using the tokens obtained by "
+        "the authentication flow, which need the permission to do so.",
+        IMPERSONATE_ARG,
+        False,
+    ))
+
     global_args.append((
         DEBUG_FLAG,
         "Output all server communication to standard error. `tx` and `rx` are placed "
@@ -2,14 +2,15 @@
 <%!
     from util import (hub_type, mangle_ident, indent_all_but_first_by, activity_rust_type, setter_fn_name, ADD_PARAM_FN,
                       upload_action_fn, is_schema_with_optionals, schema_markers, indent_by, method_default_scope,
-                      ADD_SCOPE_FN, TREF, enclose_in)
+                      ADD_SCOPE_URL_FN, TREF, enclose_in)
     from cli import (mangle_subcommand, new_method_context, PARAM_FLAG, STRUCT_FLAG, OUTPUT_FLAG, VALUE_ARG,
                      CONFIG_DIR, SCOPE_FLAG, is_request_value_property, FIELD_SEP, docopt_mode, FILE_ARG, MIME_ARG, OUT_ARG,
                      call_method_ident, POD_TYPES, opt_value, ident, JSON_TYPE_VALUE_MAP,
                      KEY_VALUE_ARG, to_cli_schema, SchemaEntry, CTYPE_POD, actual_json_type, CTYPE_MAP, CTYPE_ARRAY,
                      application_secret_path, DEBUG_FLAG, DEBUG_AUTH_FLAG, CONFIG_DIR_FLAG, req_value, MODE_ARG,
                      opt_values, SCOPE_ARG, CONFIG_DIR_ARG, DEFAULT_MIME, field_vec, comma_sep_fields, JSON_TYPE_TO_ENUM_MAP,
-                     CTYPE_TO_ENUM_MAP)
+                     CTYPE_TO_ENUM_MAP, AUTH_ARG, SERVICE_ACCOUNT_KEY_ARG, SUBJECT_ARG,
+                     IMPERSONATE_ARG)
 
     v_arg = '<%s>' % VALUE_ARG
     SOPT = 'self.opt'
@@ -33,7 +34,8 @@
 %>\
 use client::{InvalidOptionsError, CLIError, arg_from_str, writer_from_opts, parse_kv_arg,
           input_file_from_opts, input_mime_from_opts, FieldCursor, FieldError, CallType, UploadProtocol,
-          calltype_from_str, remove_json_null_values, ComplexType, JsonType, JsonTypeInfo};
+          calltype_from_str, ComplexType, JsonType, JsonTypeInfo, AuthFlow,
+          ConfigurationError};
 
 use std::default::Default;
 use std::str::FromStr;
@@ -104,23 +106,45 @@ impl<'n> Engine<'n> {
 
     // Please note that this call will fail if any part of the opt can't be handled
     async fn new(opt: ArgMatches<'n>) -> Result<Engine<'n>, InvalidOptionsError> {
-        let (config_dir, secret) = {
-            let config_dir = match client::assure_config_dir_exists(opt.value_of("${CONFIG_DIR_ARG}").unwrap_or("${CONFIG_DIR}")) {
-                Err(e) => return Err(InvalidOptionsError::single(e, 3)),
-                Ok(p) => p,
-            };
-
-            match client::application_secret_from_directory(&config_dir, "${application_secret_path(util.program_name())}",
-                                                         "${api.credentials.replace('"', r'\"')}") {
-                Ok(secret) => (config_dir, secret),
-                Err(e) => return Err(InvalidOptionsError::single(e, 4))
-            }
+        let config_dir = match client::assure_config_dir_exists(opt.value_of("${CONFIG_DIR_ARG}").unwrap_or("${CONFIG_DIR}")) {
+            Err(e) => return Err(InvalidOptionsError::single(e, 3)),
+            Ok(p) => p,
         };
 
-        let auth = yup_oauth2::InstalledFlowAuthenticator::builder(
-            secret,
-            yup_oauth2::InstalledFlowReturnMethod::HTTPRedirect,
-        ).persist_tokens_to_disk(format!("{}/${util.program_name()}", config_dir)).build().await.unwrap();
+        let service_account_key = opt.value_of("${SERVICE_ACCOUNT_KEY_ARG}");
+        let auth: Box<dyn api_client::GetToken> = match client::auth_flow_from_opts(opt.value_of("${AUTH_ARG}"), service_account_key) {
+            Err(e) => return Err(InvalidOptionsError::single(e, 4)),
+            Ok(AuthFlow::Installed) => {
+                let secret = match client::application_secret_from_directory(&config_dir, "${application_secret_path(util.program_name())}",
+                                                                             "${api.credentials.replace('"', r'\"')}") {
+                    Ok(secret) => secret,
+                    Err(e) => return Err(InvalidOptionsError::single(e, 4))
+                };
+                Box::new(yup_oauth2::InstalledFlowAuthenticator::builder(
+                    secret,
+                    yup_oauth2::InstalledFlowReturnMethod::HTTPRedirect,
+                ).persist_tokens_to_disk(format!("{}/${util.program_name()}", config_dir)).build().await.unwrap())
+            },
+            Ok(AuthFlow::ServiceAccount) => {
+                let key = match client::service_account_key_from_file(service_account_key.unwrap()) {
+                    Ok(key) => key,
+                    Err(e) => return Err(InvalidOptionsError::single(e, 4))
+                };
+                match api_client::service_account_authenticator(key, opt.value_of("${SUBJECT_ARG}")).await {
+                    Ok(auth) => Box::new(auth),
+                    Err(e) => return Err(InvalidOptionsError::single(CLIError::Configuration(ConfigurationError::Credentials(e)), 4))
+                }
+            },
+            Ok(AuthFlow::Metadata) => Box::new(api_client::MetadataServerAuthenticator::new()),
+            Ok(AuthFlow::ApplicationDefault) => match api_client::application_default_credentials().await {
+                Ok(auth) => auth,
+                Err(e) => return Err(InvalidOptionsError::single(CLIError::Configuration(ConfigurationError::Credentials(e)), 4))
+            },
+        };
+        let auth: Box<dyn api_client::GetToken> = match opt.value_of("${IMPERSONATE_ARG}") {
+            Some(target) => Box::new(api_client::ImpersonatedAuthenticator::new(auth, target)),
+            None => auth,
+        };
 
         let client =
             ${self._debug_client(DEBUG_FLAG) | indent_all_but_first_by(3)};
@@ -287,7 +311,7 @@ if dry_run {
     assert!(err.issues.len() == 0);
     % if method_default_scope(mc.m):
     for scope in ${opt_values(SCOPE_ARG, opt=SOPT)} {
-        call = call.${ADD_SCOPE_FN}(scope);
+        call = call.${ADD_SCOPE_URL_FN}(scope);
     }
     % endif
     ## Make the call, handle uploads, handle downloads (also media downloads|json decoding)
@@ -322,7 +346,7 @@ if dry_run {
             % endif
             % if mc.response_schema:
             let mut value = json::value::to_value(&output_schema).expect("serde to work");
-            remove_json_null_values(&mut value);
+            api_client::remove_json_null_values(&mut value);
             json::to_writer_pretty(&mut ostream, &value).unwrap();
             ostream.flush().unwrap();
             % endif
@@ -68,11 +68,15 @@
 	api_target = util.target_directory_name(an, version, suffix)
 	depends_on_target = ''
 	if make.depends_on_suffix is not None:
-		depends_on_target = directories.output + '/' + util.target_directory_name(an, version, make.depends_on_suffix) + CMN_SRC
+		depends_on_target = directories.output + '/' + util.target_directory_name(an, version, make.depends_on_suffix) + '/.timestamp'
 	crate_name = util.library_to_crate_name(api_name, suffix)
 	gen_root = directories.output + '/' + api_target
 	gen_root_stamp = gen_root + '/.timestamp'
 	api_common = gen_root + CMN_SRC
+	# APIs use the google-apis-common crate, only dependent crates get a copy of their client code
+	copies_common_src = make.depends_on_suffix is not None
+	if not copies_common_src:
+		api_common = gen_root_stamp
 	api_clean = api_target + '-clean'
 	api_cargo = api_target + '-cargo'
 	api_doc = api_target + '-doc'
@@ -102,10 +106,12 @@
 		print('Could not open JSON file at {}'.format(api_json))
 		print(e)
 %>\
+% if copies_common_src:
 ${api_common}: $(RUST_SRC)/${make.id}/client.rs $(lastword $(MAKEFILE_LIST)) ${gen_root_stamp}
 	@ echo "// COPY OF '$<'"  > $@
 	@ echo "// DO NOT EDIT"  >> $@
 	@cat $< >> $@
+% endif
 
 ${gen_root_stamp}: $(MAKO_RENDER) ${' '.join(i[0] for i in sds)} ${api_json_inputs} $(MAKO_STANDARD_DEPENDENCIES) ${depends_on_target}
 	@echo Generating ${api_target}
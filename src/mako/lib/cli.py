@@ -6,8 +6,7 @@ import collections
 from copy import deepcopy
 from random import (randint, random, choice)
 
-SPLIT_START = '>>>>>>>'
-SPLIT_END = '<<<<<<<'
+from util import (SPLIT_START, SPLIT_END, re_splitters)
 
 PARAM_FLAG = 'p'
 STRUCT_FLAG = 'r'
@@ -19,6 +18,10 @@ SCOPE_FLAG = 'scope'
 CONFIG_DIR_FLAG = 'config-dir'
 DEBUG_FLAG = 'debug'
 DEBUG_AUTH_FLAG = 'debug-auth'
+AUTH_FLAG = 'auth'
+SERVICE_ACCOUNT_KEY_FLAG = 'service-account-key'
+SUBJECT_FLAG = 'subject'
+IMPERSONATE_FLAG = 'impersonate-service-account'
 DEFAULT_MIME = 'application/octet-stream'
 
 MODE_ARG = 'mode'
@@ -30,6 +33,10 @@ OUT_ARG = 'out'
 
 SCOPE_ARG = 'url'
 CONFIG_DIR_ARG = 'folder'
+AUTH_ARG = 'flow'
+SERVICE_ACCOUNT_KEY_ARG = 'key-file'
+SUBJECT_ARG = 'email'
+IMPERSONATE_ARG = 'target'
 
 FIELD_SEP = '.'
 
@@ -37,8 +44,6 @@ CONFIG_DIR = '~/.google-service-cli'
 
 POD_TYPES = set(('boolean', 'integer', 'number', 'uint32', 'double', 'float', 'int32', 'int64', 'uint64', 'string'))
 
-re_splitters = re.compile(r"%s ([\w\-\.]+)\n(.*?)\n%s" % (SPLIT_START, SPLIT_END), re.MULTILINE|re.DOTALL)
-
 MethodContext = collections.namedtuple('MethodContext', ['m', 'response_schema', 'params', 'request_value',
                                                          'media_params' ,'required_props', 'optional_props',
                                                          'part_prop'])
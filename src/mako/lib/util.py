@@ -12,7 +12,12 @@ re_spaces_after_newline = re.compile('^ {4}', flags=re.MULTILINE)
 re_first_4_spaces = re.compile('^ {1,4}', flags=re.MULTILINE)
 re_desc_parts = re.compile("((the part (names|properties) that you can include in the parameter value are)|(supported values are ))(.*?)\.", flags=re.IGNORECASE|re.MULTILINE)
 
-re_find_replacements = re.compile("\{[/\+]?\w+\*?\}")
+re_find_replacements = re.compile(r"\{[+#./;?&]?\w+\*?\}")
+
+# Sections of a template's result between these markers are written to the file named after SPLIT_START
+SPLIT_START = '>>>>>>>'
+SPLIT_END = '<<<<<<<'
+re_splitters = re.compile(r"%s ([\w\-\.]+)\n(.*?)\n%s" % (SPLIT_START, SPLIT_END), re.MULTILINE|re.DOTALL)
 
 HTTP_METHODS = set(("OPTIONS", "GET", "POST", "PUT", "DELETE", "HEAD", "TRACE", "CONNECT", "PATCH" ))
 
@@ -37,6 +42,9 @@ RESERVED_WORDS = set(('abstract', 'alignof', 'as', 'become', 'box', 'break', 'co
                       'return', 'sizeof', 'static', 'self', 'struct', 'super', 'true', 'trait', 'type', 'typeof',
                       'unsafe', 'unsized', 'use', 'virtual', 'where', 'while', 'yield'))
 
+# Names taken in the root of a generated crate, which resource modules must not use
+CRATE_ROOT_NAMES = set(('api', 'client', 'lib', 'hyper', 'serde', 'serde_json', 'oauth2', 'mime', 'url'))
+
 words = [w.strip(',') for w in "Lorem ipsum dolor sit amet, consetetur sadipscing elitr, sed diam nonumy eirmod tempor invidunt ut labore et dolore magna aliquyam erat, sed diam voluptua. At vero eos et accusam et justo duo dolores et ea rebum. Stet clita kasd gubergren, no sea takimata sanctus est Lorem ipsum dolor sit amet. Lorem ipsum dolor sit amet, consetetur sadipscing elitr, sed diam nonumy eirmod tempor invidunt ut labore et dolore magna aliquyam erat, sed diam voluptua. At vero eos et accusam et justo duo dolores et ea rebum. Stet clita kasd gubergren, no sea takimata sanctus est Lorem ipsum dolor sit amet.".split(' ')]
 RUST_TYPE_RND_MAP = {'bool': lambda: str(bool(randint(0, 1))).lower(),
                      'u32' : lambda: randint(0, 100),
@@ -57,12 +65,14 @@ METHODS_RESOURCE = 'methods'
 
 ADD_PARAM_FN = 'param'
 ADD_SCOPE_FN = 'add_scope'
+ADD_SCOPE_URL_FN = 'add_scope_url'
+NULL_FIELD_FN = 'null_field'
 ADD_PARAM_MEDIA_EXAMPLE = "." + ADD_PARAM_FN + '("alt", "media")'
 
 SPACES_PER_TAB = 4
 
 NESTED_TYPE_SUFFIX = 'item'
-DELEGATE_TYPE = 'client::Delegate'
+DELEGATE_TYPE = 'client::AsyncDelegate'
 REQUEST_PRIORITY = 100
 REQUEST_MARKER_TRAIT = 'client::RequestValue'
 RESPONSE_MARKER_TRAIT = 'client::ResponseResult'
@@ -85,6 +95,15 @@ If the upload fails for whichever reason, all progress is lost.""",
         'suffix': '',
         'example_value': 'fs::File::open("file.ext").unwrap(), "application/octet-stream".parse().unwrap()'
     },
+    'media' : {
+        'arg_name': 'stream',
+        'description': """Upload media all at once, as the raw body of the request.
+No request value is sent along with it, which avoids the JSON part of the *simple* protocol.
+If the upload fails for whichever reason, all progress is lost.""",
+        'default': 'fs::File',
+        'suffix': '_media',
+        'example_value': 'fs::File::open("file.ext").unwrap(), "application/octet-stream".parse().unwrap()'
+    },
     'resumable' : {
         'arg_name': 'resumeable_stream',
         'description': """Upload media in a resumable fashion.
@@ -435,6 +454,41 @@ def _traverse_schema_ids(s, c):
     # end gather usages
     return ids
 
+# Return the sorted names of all resources with methods using the given schema, directly or as part of another one
+def schema_resources(s, c):
+    resources = set()
+    for sid in _traverse_schema_ids(s, c):
+        for fqan in c.sta_map.get(sid, dict()):
+            resources.add(activity_split(fqan)[1])
+    return sorted(resources)
+
+# Return the name of the cargo feature enabling the given resource
+def resource_feature(resource):
+    return resource
+
+# Return the name of the module, and thus of the file, holding all builders of the given resource
+def resource_module(resource):
+    n = mangle_ident(resource)
+    if n in CRATE_ROOT_NAMES:
+        return n + '_'
+    return n
+
+# Return the cfg predicate enabling code only if one of the given resources is, or '' if it's always enabled
+def resources_cfg(resources):
+    features = ['feature = "%s"' % resource_feature(r) for r in resources]
+    if len(features) > 1:
+        return 'any(%s)' % ', '.join(features)
+    return ''.join(features)
+
+# Return the #[cfg(...)] attribute line enabling an item only if all given predicates are true, or ''
+def cfg_attribute(*predicates):
+    predicates = [p for p in predicates if p]
+    if not predicates:
+        return ''
+    if len(predicates) > 1:
+        return '#[cfg(all(%s))]\n' % ', '.join(predicates)
+    return '#[cfg(%s)]\n' % predicates[0]
+
 # Return sorted type names of all markers applicable to the given schema
 # This list is transitive. Thus, if the schema is used as child of someone with a trait, it
 # inherits this trait
@@ -479,6 +533,130 @@ def schema_markers(s, c, transitive=True):
 
 ## -- End Rust TypeSystem -- @}
 
+# Collections which are commonly at the root of resource names, and thus don't make up their type name
+RESOURCE_NAME_ROOT_COLLECTIONS = ('projects', 'locations', 'organizations', 'folders', 'billingAccounts')
+re_resource_name_collection = re.compile(r'([A-Za-z][A-Za-z0-9]*)/\[\^/\]\+')
+re_resource_name_pattern = re.compile(r'^\^([A-Za-z][A-Za-z0-9]*/\[\^/\]\+(?:/[A-Za-z][A-Za-z0-9]*/\[\^/\]\+)*)\$$')
+
+ResourceName = collections.namedtuple('ResourceName', ['type_name', 'collections', 'ids', 'template'])
+
+# Return the pattern of a parameter if it is a resource name made of collections and ids only,
+# like '^projects/[^/]+/topics/[^/]+$', or None
+def resource_name_pattern(p):
+    if p.get('location') != 'path' or not re_resource_name_pattern.match(p.get('pattern', '')):
+        return None
+    return p.pattern
+
+# Return { pattern -> ResourceName } for all resource names used by the methods of the API
+def resource_names(c):
+    if id(c) in _resource_names_cache:
+        return _resource_names_cache[id(c)][1]
+    by_pattern = dict()
+    for m in c.fqan_map.values():
+        for p in m.get('parameters', dict()).values():
+            pattern = resource_name_pattern(p)
+            if not pattern or pattern in by_pattern:
+                continue
+            collections_ = re_resource_name_collection.findall(pattern)
+            ids = [mangle_ident(singular(cn)) for cn in collections_]
+            # ids must work as arguments and accessors of the resource name type
+            if len(set(ids)) != len(ids) or set(ids) & set(('new', 'as_str')):
+                continue
+            by_pattern[pattern] = (collections_, ids)
+    # end for each method
+
+    def type_name(collections_, fully_qualified):
+        names = [singular(cn) for cn in collections_]
+        if fully_qualified:
+            return ''.join(canonical_type_name(n) for n in names) + 'Name'
+        # generic names like 'versions' are qualified by their parent, unless that's a root collection
+        tn = names[-1]
+        if len(names) > 1 and collections_[-2] not in RESOURCE_NAME_ROOT_COLLECTIONS and tn.islower():
+            tn = names[-2] + capitalize(tn)
+        return canonical_type_name(tn) + 'Name'
+
+    short_names = collections.Counter(type_name(cs, False) for cs, _ in by_pattern.values())
+    res = dict()
+    for pattern, (collections_, ids) in by_pattern.items():
+        tn = type_name(collections_, False)
+        if short_names[tn] > 1 or tn in c.schemas:
+            tn = type_name(collections_, True)
+        if tn in c.schemas:
+            continue
+        template = '/'.join('%s/{%s}' % (cn, id) for cn, id in zip(collections_, ids))
+        res[pattern] = ResourceName(tn, collections_, ids, template)
+    # keep the context alive, to be sure its id isn't reused
+    _resource_names_cache[id(c)] = (c, res)
+    return res
+_resource_names_cache = dict()
+
+# Return True if a field selector should be generated for the given schema, which is the case for all objects
+# that can be part of a response
+def is_field_selector_schema(s, c):
+    return s.type == 'object' and bool(s.get('properties')) and \
+           RESPONSE_MARKER_TRAIT in schema_markers(s, c, transitive=True)
+
+# Return the id of the schema whose fields can be selected within the given property, possibly through an array,
+# or None if the property can only be selected as a whole
+def field_selector_schema_id(schemas, c, sn, pn, p):
+    if p.get('type') == 'object' and is_map_prop(p):
+        return None
+    m = re.match(r'^(?:Vec<)?(?:Option<Box<)?(\w+)>*$', to_rust_type(schemas, sn, pn, p, allow_optionals=False))
+    if m and m.group(1) in c.schemas and is_field_selector_schema(c.schemas[m.group(1)], c):
+        return m.group(1)
+    return None
+
+# Rust types whose values can be checked against the 'minimum' and 'maximum' of a parameter or property
+NUMERIC_RUST_TYPES = ('u32', 'u64', 'i32', 'i64', 'f32', 'f64')
+
+# Return True if the schema is sent to the server, which makes it implement client::Validate
+def is_validated_schema(s, c):
+    return s.type == 'object' and bool(s.get('properties')) and \
+           REQUEST_MARKER_TRAIT in schema_markers(s, c, transitive=True)
+
+# Return the id of the validated schema within the given property, possibly through arrays and maps,
+# or None if the property doesn't contain one
+def validated_schema_id(schemas, c, sn, pn, p):
+    m = re.match(r'^(?:Vec<|HashMap<String, )*(?:Option<Box<)?(\w+)>*$',
+                 to_rust_type(schemas, sn, pn, p, allow_optionals=False))
+    if m and m.group(1) in c.schemas and is_validated_schema(c.schemas[m.group(1)], c):
+        return m.group(1)
+    return None
+
+re_output_only_description = re.compile(r'^\s*\[?(output[ -]only|read-only)\b', re.IGNORECASE)
+
+# Return True if the server sets the property, and ignores what the client sends
+def is_output_only_property(p):
+    return bool(p.get('readOnly')) or bool(re_output_only_description.match(p.get('description', '')))
+
+# Return the calls to a client::Validator named `validator` which check a single value of the given parameter or
+# property against its enumeration, pattern and bounds. `field` is the rust expression of the field path as &str,
+# `value` the one of a reference to the value. With `check_enum` False, the enumeration isn't checked.
+def validation_checks(p, field, value, check_enum=True):
+    if p.get('type') == 'array':
+        p = p.get('items', dict())
+    if TREF in p or p.get('type') not in TYPE_MAP:
+        return []
+    rust_type = TYPE_MAP[p['type']]
+    if rust_type == USE_FORMAT:
+        rust_type = TYPE_MAP.get(p.get('format'))
+
+    checks = []
+    if rust_type == 'String':
+        if check_enum and p.get('enum'):
+            checks.append('validator.one_of(%s, %s, &[%s]);'
+                          % (field, value, ', '.join('"%s"' % v.replace('\\', '\\\\').replace('"', '\\"')
+                                                     for v in p['enum'])))
+        if p.get('pattern'):
+            checks.append('validator.pattern(%s, %s, r#"%s"#);' % (field, value, p['pattern']))
+    elif rust_type in NUMERIC_RUST_TYPES and ('minimum' in p or 'maximum' in p):
+        bound = lambda n: n in p and 'Some(%r)' % float(p[n]) or 'None'
+        number = value.startswith('&') and value[1:] or '*' + value
+        if rust_type != 'f64':
+            number += ' as f64'
+        checks.append('validator.range(%s, %s, %s, %s);' % (field, number, bound('minimum'), bound('maximum')))
+    return checks
+
 # NOTE: unfortunately, it turned out that sometimes fields are missing. The only way to handle this is to
 # use optionals everywhere. If that should ever change, we can make a decision here based on the
 # non-transitive markers that we get here !
@@ -580,6 +758,40 @@ def is_required_property(p):
 def is_repeated_property(p):
     return p.get('repeated', False)
 
+# Global parameters which are set by the library itself, or by other means than their own setter
+GLOBAL_PARAMS_WITHOUT_SETTER = ('key', 'oauth_token', 'access_token', 'bearer_token', 'uploadType', 'callback',
+                                'fields')
+
+# Return [(name, param), ...] of all global parameters which get a typed setter on every call builder
+def global_params_with_setter(parameters):
+    return [(pn, p) for pn, p in sorted((parameters or dict()).items()) if pn not in GLOBAL_PARAMS_WITHOUT_SETTER]
+
+# Return the name of the setter of a global parameter, e.g. 'xgafv' for '$.xgafv'
+def global_param_fn_name(pn):
+    return mangle_ident(pn).lstrip('_')
+
+# Return the name of the enum holding the possible values of a global parameter
+def global_param_enum_type(schemas, pn):
+    tn = canonical_type_name(global_param_fn_name(pn))
+    if tn in schemas:
+        tn += 'Param'
+    return tn
+
+# Return [(value, description), ...] of all possible values of a global parameter.
+# Some APIs don't list 'media' as value of 'alt' even though they support media downloads
+def global_param_enum_values(pn, p):
+    descriptions = p.get('enumDescriptions', list())
+    res = [(v, i < len(descriptions) and descriptions[i] or v) for i, v in enumerate(p.get('enum', list()))]
+    if pn == 'alt' and res and 'media' not in p.enum:
+        res.append(('media', 'Media download with context-dependent Content-Type'))
+    return res
+
+# Return the enum variant for the given value of a global parameter, e.g. 'V1' for '1'
+def global_param_enum_variant(v):
+    if v[:1].isdigit():
+        return 'V' + v
+    return canonical_type_name(v)
+
 def setter_fn_name(p):
     fn_name = p.name
     if is_repeated_property(p):
@@ -616,20 +828,26 @@ def method_media_params(m):
     # runtime will have to check
     res = list()
     for pn, proto in mu.protocols.items():
-        # the pi (proto-info) dict can be shown to the user
-        pi = {'multipart': proto.multipart and 'yes' or 'no', 'maxSize': mu.get('maxSize', '0kb'), 'validMimeTypes': mu.accept}
-        try:
-            ti = type(m)(PROTOCOL_TYPE_INFO[pn])
-        except KeyError:
+        if pn not in PROTOCOL_TYPE_INFO or pn == 'media':
             raise AssertionError("media upload protocol '%s' is not implemented" % pn)
-        p = type(m)({'name': 'media_%s',
-             'info': pi,
-             'protocol': pn,
-             'path': proto.path,
-             'type': ti,
-             'description': ti.description,
-             'max_size': size_to_bytes(mu.get('maxSize', '0kb'))})
-        res.append(p)
+        protocols = [(pn, proto.multipart)]
+        if pn == 'simple':
+            # the endpoint of the simple protocol also takes the raw media, which is uploadType=media
+            protocols.append(('media', False))
+        for protocol, multipart in protocols:
+            # the pi (proto-info) dict can be shown to the user
+            pi = {'multipart': multipart and 'yes' or 'no', 'maxSize': mu.get('maxSize', '0kb'), 'validMimeTypes': mu.accept}
+            ti = type(m)(PROTOCOL_TYPE_INFO[protocol])
+            p = type(m)({'name': 'media_%s',
+                 'info': pi,
+                 'protocol': protocol,
+                 'path': proto.path,
+                 'type': ti,
+                 'description': ti.description,
+                 'accept': mu.accept,
+                 'max_size': size_to_bytes(mu.get('maxSize', '0kb'))})
+            res.append(p)
+        # end for each protocol served by the endpoint
     # end for each proto
 
     return res
@@ -905,20 +1123,48 @@ def hub_type_bounds():
 def supports_scopes(auth):
     return bool(auth) and bool(auth.oauth2)
 
-# Returns th desired scope for the given method. It will use read-only scopes for read-only methods
+# Return True if the scope with the given url only grants read access
+def is_read_only_scope(url):
+    return re.search(r'read[-_.]?only', url) is not None
+
+# Return the urls of the scopes authorizing the given method, the least privileged one first.
+# Read-only scopes come first. Within read-only and other scopes, the ones of the service come before the ones which
+# encompass others, like 'cloud-platform.read-only', or 'https://www.googleapis.com/auth/drive' in presence of
+# 'https://www.googleapis.com/auth/drive.file'.
+def method_scopes(m):
+    scopes = m.get('scopes', list())
+
+    # the name of the scope without its read-only suffix, like 'drive' for '.../auth/drive.readonly'
+    def name(url):
+        return re.sub(r'[-_.]?read[-_.]?only$', '', os.path.basename(url))
+
+    # a scope is broad if its name is the prefix of the name of another scope of the same tier
+    def is_broad(url):
+        return 'full' in name(url) or name(url) == 'cloud-platform' or \
+               any(name(s).startswith(name(url) + '.') for s in scopes
+                   if s != url and is_read_only_scope(s) == is_read_only_scope(url))
+    return sorted(scopes, key=lambda url: (not is_read_only_scope(url), is_broad(url), url))
+
+# Returns th desired scope for the given method, which is the least privileged one authorizing it.
 # May be None no scope-based authentication is required
 def method_default_scope(m):
-    if 'scopes' not in m:
+    scopes = method_scopes(m)
+    if not scopes:
         return None
-    default_scope = sorted(m.scopes)[0]
+    return scopes[0]
+
+# The names of methods which read data even though they are called with POST, as they take their arguments as
+# request value. Others, like 'watch', create resources on the server.
+READ_ONLY_POST_METHODS = ('get', 'list', 'search', 'batchGet', 'lookup', 'getIamPolicy', 'testIamPermissions')
+
+# Return True if the method may change data, which is when it doesn't use a reading http method. POST methods
+# only read if they are known to, and a read-only scope authorizes them.
+def is_mutating_method(m):
     if m.httpMethod in ('HEAD', 'GET', 'OPTIONS', 'TRACE'):
-        for scope in m.scopes:
-            if 'readonly' in scope:
-                default_scope = scope
-                break
-        # end for each scope
-    # end try to find read-only default scope
-    return default_scope
+        return False
+    if m.httpMethod == 'POST' and m.id.split('.')[-1] in READ_ONLY_POST_METHODS:
+        return not any(is_read_only_scope(url) for url in m.get('scopes', list()))
+    return True
 
 # return list of type bounds required by method builder
 def mb_type_bounds():
@@ -1053,5 +1299,28 @@ def size_to_bytes(size):
     # end handle errors gracefully
 
 
+# Post-processor for mako-render: writes all split sections of the result next to the output file,
+# and returns the remaining result, or None if there is nothing left to write.
+def process_template_result(r, output_file):
+    sections = list(re_splitters.finditer(r))
+    if not sections:
+        return r
+
+    dir = output_file and os.path.dirname(output_file) or '.'
+    if not os.path.isdir(dir):
+        os.makedirs(dir)
+    # end handle output directory
+
+    for m in sections:
+        with open(os.path.join(dir, m.group(1)), 'wb') as fh:
+            fh.write(m.group(2).encode('UTF-8'))
+    # end for each match
+
+    r = re_splitters.sub('', r).rstrip() + '\n'
+    if not r.strip():
+        return None
+    return r
+
+
 if __name__ == '__main__':
     raise AssertionError('For import only')
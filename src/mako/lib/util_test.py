@@ -2,9 +2,16 @@
 
 import unittest
 import json
+import os
+import tempfile
 import importlib_resources
 
-from .util import to_api_version, library_name, re_find_replacements, to_rust_type, new_context
+from .util import (to_api_version, library_name, re_find_replacements, to_rust_type, new_context,
+                   resource_names, Context, validation_checks, is_output_only_property,
+                   method_scopes, method_default_scope, is_mutating_method, resource_module,
+                   process_template_result, SPLIT_START, SPLIT_END, global_params_with_setter,
+                   global_param_fn_name, global_param_enum_type, global_param_enum_variant,
+                   global_param_enum_values)
 from . import test_data
 
 
@@ -12,6 +19,23 @@ def read_test_json_file(resource):
     data = importlib_resources.read_text(test_data, resource)
     return json.loads(data)
 
+class AttrDict(dict):
+    """A dict whose items are attributes as well, like the ones the templates get."""
+    def __init__(self, d):
+        super().__init__((k, AttrDict(v) if isinstance(v, dict) else v) for k, v in d.items())
+
+    def __getattr__(self, name):
+        try:
+            return self[name]
+        except KeyError:
+            raise AttributeError(name)
+
+
+def context_with_methods(methods, schemas=None):
+    methods = [AttrDict(m) for m in methods]
+    return Context(dict(), dict((m.id, m) for m in methods), dict(), dict(), schemas or dict())
+
+
 class UtilsTest(unittest.TestCase):
 
     def test_to_version_ok(self):
@@ -111,6 +135,135 @@ class UtilsTest(unittest.TestCase):
         rust_type = to_rust_type(schemas, class_name, property_name, property_value, allow_optionals=True)
         self.assertEqual(rust_type, 'Option<Vec<HashMap<String, String>>>')
 
+    def test_resource_names(self):
+        def path_param(pattern):
+            return {'location': 'path', 'type': 'string', 'pattern': pattern}
+        c = context_with_methods([
+            {'id': 'secretmanager.projects.secrets.versions.access',
+             'parameters': {'name': path_param('^projects/[^/]+/secrets/[^/]+/versions/[^/]+$')}},
+            {'id': 'pubsub.projects.topics.get',
+             'parameters': {'topic': path_param('^projects/[^/]+/topics/[^/]+$'),
+                            'query': {'location': 'query', 'type': 'string',
+                                      'pattern': '^projects/[^/]+/other/[^/]+$'}}},
+            {'id': 'pubsub.projects.snapshots.get',
+             'parameters': {'snapshot': path_param('^projects/[^/]+/snapshots/.*$')}},
+        ], schemas={'Topic': {}, 'TopicName': {}})
+        names = resource_names(c)
+
+        self.assertEqual(len(names), 2)
+        rn = names['^projects/[^/]+/secrets/[^/]+/versions/[^/]+$']
+        self.assertEqual(rn.type_name, 'SecretVersionName')
+        self.assertEqual(rn.collections, ['projects', 'secrets', 'versions'])
+        self.assertEqual(rn.ids, ['project', 'secret', 'version'])
+        self.assertEqual(rn.template, 'projects/{project}/secrets/{secret}/versions/{version}')
+        # names clashing with a schema are qualified by their parents
+        rn = names['^projects/[^/]+/topics/[^/]+$']
+        self.assertEqual(rn.type_name, 'ProjectTopicName')
+        self.assertEqual(rn.template, 'projects/{project}/topics/{topic}')
+
+    def test_validation_checks(self):
+        p = {'type': 'string', 'enum': ['A', 'B'], 'pattern': '^[A-Z]$'}
+        self.assertEqual(validation_checks(p, '"f"', 'value'),
+                         ['validator.one_of("f", value, &["A", "B"]);',
+                          'validator.pattern("f", value, r#"^[A-Z]$"#);'])
+        self.assertEqual(validation_checks(p, '"f"', 'value', check_enum=False),
+                         ['validator.pattern("f", value, r#"^[A-Z]$"#);'])
+        self.assertEqual(validation_checks({'type': 'integer', 'format': 'int32', 'minimum': '1'}, '"f"', '&v'),
+                         ['validator.range("f", v as f64, Some(1.0), None);'])
+
+        for description in ('Output only. The time it was created.', '[Output Only] Server-defined URL.',
+                            'Output-only. The state.', 'Read-only. The id.'):
+            self.assertTrue(is_output_only_property({'type': 'string', 'description': description}), description)
+        self.assertTrue(is_output_only_property({'type': 'string', 'readOnly': True}))
+        self.assertFalse(is_output_only_property({'type': 'string', 'description': 'The log type to enable.'}))
+
+    def test_method_scopes(self):
+        def scopes(*names):
+            return ['https://www.googleapis.com/auth/' + n for n in names]
+
+        m = AttrDict({'id': 'storage.buckets.get', 'scopes': scopes(
+            'cloud-platform', 'cloud-platform.read-only', 'devstorage.full_control', 'devstorage.read_only',
+            'devstorage.read_write')})
+        # scopes of the service come before the ones encompassing all services, within each tier
+        self.assertEqual(method_scopes(m), scopes(
+            'devstorage.read_only', 'cloud-platform.read-only', 'devstorage.read_write', 'cloud-platform',
+            'devstorage.full_control'))
+        self.assertEqual(method_default_scope(m), 'https://www.googleapis.com/auth/devstorage.read_only')
+
+        m = AttrDict({'id': 'drive.files.get', 'scopes': scopes(
+            'drive', 'drive.file', 'drive.readonly', 'drive.metadata.readonly', 'drive.metadata')})
+        # a scope whose name is the prefix of another one's encompasses it
+        self.assertEqual(method_scopes(m), scopes(
+            'drive.metadata.readonly', 'drive.readonly', 'drive.file', 'drive.metadata', 'drive'))
+
+        self.assertIsNone(method_default_scope(AttrDict({'id': 'discovery.apis.list'})))
+
+    def test_is_mutating_method(self):
+        def method(mid, http_method, *scopes):
+            return AttrDict({'id': mid, 'httpMethod': http_method,
+                             'scopes': ['https://www.googleapis.com/auth/' + s for s in scopes]})
+
+        self.assertFalse(is_mutating_method(method('storage.objects.get', 'GET', 'devstorage.full_control')))
+        self.assertFalse(is_mutating_method(method('storage.objects.list', 'GET')))
+        self.assertTrue(is_mutating_method(method('storage.objects.insert', 'POST', 'devstorage.read_write')))
+        self.assertTrue(is_mutating_method(method('storage.objects.delete', 'DELETE', 'devstorage.read_write')))
+        # POST methods which create resources, even though a read-only scope authorizes them
+        self.assertTrue(is_mutating_method(method('storage.objects.watchAll', 'POST', 'devstorage.read_only')))
+        self.assertTrue(is_mutating_method(method('bigquery.jobs.query', 'POST', 'bigquery.readonly')))
+        # POST methods known to only read
+        self.assertFalse(is_mutating_method(method('youtube.search.list', 'POST', 'youtube.readonly')))
+        self.assertFalse(is_mutating_method(
+            method('storage.buckets.testIamPermissions', 'POST', 'devstorage.read_only')))
+        self.assertTrue(is_mutating_method(method('pubsub.projects.topics.getIamPolicy', 'POST', 'pubsub')))
+        self.assertTrue(is_mutating_method(method('storage.buckets.get', 'PUT', 'devstorage.read_only')))
+
+    def test_resource_module(self):
+        self.assertEqual(resource_module('bucketAccessControls'), 'bucket_access_controls')
+        self.assertEqual(resource_module('type'), 'type_')
+        # names taken in the root of the crate
+        self.assertEqual(resource_module('api'), 'api_')
+        self.assertEqual(resource_module('url'), 'url_')
+
+    def test_process_template_result(self):
+        with tempfile.TemporaryDirectory() as dir:
+            output_file = os.path.join(dir, 'src', 'api.rs')
+            r = "pub use crate::a::*;\n%s a.rs\nmod a;\n%s\n%s b.rs\nmod b;\n%s\n" % ((SPLIT_START, SPLIT_END) * 2)
+            self.assertEqual(process_template_result(r, output_file), "pub use crate::a::*;\n")
+            for name in ('a', 'b'):
+                with open(os.path.join(dir, 'src', name + '.rs')) as fh:
+                    self.assertEqual(fh.read(), "mod %s;" % name)
+
+            self.assertIsNone(process_template_result("%s c.rs\n\n%s\n" % (SPLIT_START, SPLIT_END), output_file))
+            self.assertEqual(process_template_result("", output_file), "")
+
+    def test_global_params_with_setter(self):
+        parameters = AttrDict(read_test_json_file('photoslibrary-api.json')['parameters'])
+        params = dict(global_params_with_setter(parameters))
+        self.assertEqual(sorted(params), ['$.xgafv', 'alt', 'prettyPrint', 'quotaUser', 'upload_protocol'])
+        self.assertEqual(global_param_fn_name('$.xgafv'), 'xgafv')
+        self.assertEqual(global_param_fn_name('upload_protocol'), 'upload_protocol')
+        self.assertEqual(global_params_with_setter(None), [])
+
+        # only parameters with an enum get one
+        self.assertEqual(global_param_enum_type(dict(), 'alt'), 'Alt')
+        self.assertEqual(global_param_enum_type(dict(), '$.xgafv'), 'Xgafv')
+        self.assertNotIn('enum', params['upload_protocol'])
+        # unless a schema of the API has its name already
+        self.assertEqual(global_param_enum_type({'Alt': dict()}, 'alt'), 'AltParam')
+
+        self.assertEqual(global_param_enum_values('$.xgafv', params['$.xgafv']),
+                         [('1', 'v1 error format'), ('2', 'v2 error format')])
+        self.assertEqual([v for v, _ in global_param_enum_values('alt', params['alt'])], ['json', 'media', 'proto'])
+        # 'media' is added to the values of 'alt' if the API doesn't list it
+        alt = AttrDict({'type': 'string', 'enum': ['json']})
+        self.assertEqual(global_param_enum_values('alt', alt),
+                         [('json', 'json'), ('media', 'Media download with context-dependent Content-Type')])
+        self.assertEqual(global_param_enum_values('upload_protocol', params['upload_protocol']), [])
+
+        self.assertEqual(global_param_enum_variant('1'), 'V1')
+        self.assertEqual(global_param_enum_variant('json'), 'Json')
+        self.assertEqual(global_param_enum_variant('media'), 'Media')
+
 
 def main():
     unittest.main()
@@ -1,6 +1,6 @@
 use clap::{App, SubCommand};
 use mime::Mime;
-use oauth2::{ApplicationSecret, ConsoleApplicationSecret};
+use oauth2::{ApplicationSecret, ConsoleApplicationSecret, ServiceAccountKey};
 use serde_json as json;
 use serde_json::value::Value;
 use strsim;
@@ -45,40 +45,6 @@ pub struct JsonTypeInfo {
     pub ctype: ComplexType,
 }
 
-// Based on @erickt user comment. Thanks for the idea !
-// Remove all keys whose values are null from given value (changed in place)
-pub fn remove_json_null_values(value: &mut Value) {
-    match *value {
-        Value::Object(ref mut map) => {
-            let mut for_removal = Vec::new();
-
-            for (key, mut value) in map.iter_mut() {
-                if value.is_null() {
-                    for_removal.push(key.clone());
-                } else {
-                    remove_json_null_values(&mut value);
-                }
-            }
-
-            for key in &for_removal {
-                map.remove(key);
-            }
-        }
-        json::value::Value::Array(ref mut arr) => {
-            let mut i = 0;
-            while i < arr.len() {
-                if arr[i].is_null() {
-                    arr.remove(i);
-                } else {
-                    remove_json_null_values(&mut arr[i]);
-                    i += 1;
-                }
-            }
-        }
-        _ => {}
-    }
-}
-
 fn did_you_mean<'a>(v: &str, possible_values: &[&'a str]) -> Option<&'a str> {
     let mut candidate: Option<(f64, &str)> = None;
     for pv in possible_values {
@@ -102,6 +68,7 @@ pub enum CallType {
 arg_enum! {
     pub enum UploadProtocol {
         Simple,
+        Media,
         Resumable
     }
 }
@@ -110,6 +77,7 @@ impl AsRef<str> for UploadProtocol {
     fn as_ref(&self) -> &str {
         match *self {
             UploadProtocol::Simple => "simple",
+            UploadProtocol::Media => "media",
             UploadProtocol::Resumable => "resumable",
         }
     }
@@ -127,9 +95,9 @@ impl AsRef<str> for CallType {
 #[derive(Clone, Default)]
 pub struct FieldCursor(Vec<String>);
 
-impl ToString for FieldCursor {
-    fn to_string(&self) -> String {
-        self.0.join(".")
+impl fmt::Display for FieldCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.join("."))
     }
 }
 
@@ -141,7 +109,7 @@ impl From<&'static str> for FieldCursor {
     }
 }
 
-fn assure_entry<'a, 'b>(m: &'a mut json::Map<String, Value>, k: &'b String) -> &'a mut Value {
+fn assure_entry<'a>(m: &'a mut json::Map<String, Value>, k: &String) -> &'a mut Value {
     if m.contains_key(k) {
         return m.get_mut(k).expect("value to exist");
     }
@@ -151,7 +119,7 @@ fn assure_entry<'a, 'b>(m: &'a mut json::Map<String, Value>, k: &'b String) -> &
 
 impl FieldCursor {
     pub fn set(&mut self, value: &str) -> Result<(), CLIError> {
-        if value.len() == 0 {
+        if value.is_empty() {
             return Err(CLIError::Field(FieldError::Empty));
         }
 
@@ -164,7 +132,7 @@ impl FieldCursor {
         let mut fields = self.0.clone();
 
         let push_field = |fs: &mut Vec<String>, f: &mut String| {
-            if f.len() > 0 {
+            if !f.is_empty() {
                 fs.push(f.clone());
                 f.truncate(0);
             }
@@ -187,10 +155,8 @@ impl FieldCursor {
                 }
             } else {
                 num_conscutive_field_seps = 0;
-                if cid == 1 {
-                    if first_is_field_sep {
-                        fields.truncate(0);
-                    }
+                if cid == 1 && first_is_field_sep {
+                    fields.truncate(0);
                 }
                 field.push(c);
             }
@@ -214,7 +180,7 @@ impl FieldCursor {
     }
 
     pub fn did_you_mean(value: &str, possible_values: &[&str]) -> Option<String> {
-        if value.len() == 0 {
+        if value.is_empty() {
             return None;
         }
 
@@ -224,10 +190,10 @@ impl FieldCursor {
         let mut output = String::new();
 
         let push_field = |fs: &mut String, f: &mut String| {
-            if f.len() > 0 {
-                fs.push_str(match did_you_mean(&f, possible_values) {
+            if !f.is_empty() {
+                fs.push_str(match did_you_mean(f, possible_values) {
                     Some(candidate) => candidate,
-                    None => &f,
+                    None => f,
                 });
                 f.truncate(0);
             }
@@ -248,7 +214,7 @@ impl FieldCursor {
 
         push_field(&mut output, &mut field);
 
-        if &output == value {
+        if output == value {
             None
         } else {
             Some(output)
@@ -263,12 +229,12 @@ impl FieldCursor {
         err: &mut InvalidOptionsError,
         orig_cursor: &FieldCursor,
     ) {
-        assert!(self.0.len() > 0);
+        assert!(!self.0.is_empty());
 
         for field in &self.0[..self.0.len() - 1] {
             let tmp = object;
             object = match *tmp {
-                Value::Object(ref mut mapping) => assure_entry(mapping, &field),
+                Value::Object(ref mut mapping) => assure_entry(mapping, field),
                 _ => panic!("We don't expect non-object Values here ..."),
             };
         }
@@ -280,18 +246,18 @@ impl FieldCursor {
                     |value: &str, jtype: JsonType, err: &mut InvalidOptionsError| -> Value {
                         match jtype {
                             JsonType::Boolean => {
-                                Value::Bool(arg_from_str(value, err, &field, "boolean"))
+                                Value::Bool(arg_from_str(value, err, field, "boolean"))
                             }
                             JsonType::Int => Value::Number(
-                                json::Number::from_f64(arg_from_str(value, err, &field, "int"))
+                                json::Number::from_f64(arg_from_str(value, err, field, "int"))
                                     .expect("valid f64"),
                             ),
                             JsonType::Uint => Value::Number(
-                                json::Number::from_f64(arg_from_str(value, err, &field, "uint"))
+                                json::Number::from_f64(arg_from_str(value, err, field, "uint"))
                                     .expect("valid f64"),
                             ),
                             JsonType::Float => Value::Number(
-                                json::Number::from_f64(arg_from_str(value, err, &field, "float"))
+                                json::Number::from_f64(arg_from_str(value, err, field, "float"))
                                     .expect("valid f64"),
                             ),
                             JsonType::String => Value::String(value.to_owned()),
@@ -319,7 +285,7 @@ impl FieldCursor {
                         let (key, value) = parse_kv_arg(value, err, true);
                         let jval = to_jval(value.unwrap_or(""), type_info.jtype, err);
 
-                        match *assure_entry(mapping, &field) {
+                        match *assure_entry(mapping, field) {
                             Value::Object(ref mut value_map) => {
                                 if value_map.insert(key.to_owned(), jval).is_some() {
                                     err.issues.push(CLIError::Field(FieldError::Duplicate(
@@ -353,7 +319,7 @@ pub fn parse_kv_arg<'a>(
     match kv.find('=') {
         None => {
             add_err();
-            return (kv, None);
+            (kv, None)
         }
         Some(pos) => {
             let key = &kv[..pos];
@@ -477,6 +443,9 @@ pub enum ConfigurationError {
     HomeExpansionFailed(String),
     Secret(ApplicationSecretError),
     Io((String, io::Error)),
+    UnknownAuthFlow(String),
+    ServiceAccountKeyUnset,
+    Credentials(io::Error),
 }
 
 impl fmt::Display for ConfigurationError {
@@ -499,6 +468,19 @@ impl fmt::Display for ConfigurationError {
                 "IO operation failed on path '{}' with error: {}.",
                 path, err
             ),
+            ConfigurationError::UnknownAuthFlow(ref flow) => writeln!(
+                f,
+                "'{}' is not a valid authentication flow. Choose from one of {}.",
+                flow,
+                AuthFlow::variants().join(", ")
+            ),
+            ConfigurationError::ServiceAccountKeyUnset => writeln!(
+                f,
+                "--service-account-key must be set to use the service-account flow."
+            ),
+            ConfigurationError::Credentials(ref err) => {
+                writeln!(f, "Credentials could not be obtained with error: {}.", err)
+            }
         }
     }
 }
@@ -594,11 +576,11 @@ impl fmt::Display for CLIError {
                 arg_name, value, type_name, err_desc
             ),
             CLIError::UnknownParameter(ref param_name, ref possible_values) => {
-                let suffix = match did_you_mean(param_name, &possible_values) {
+                let suffix = match did_you_mean(param_name, possible_values) {
                     Some(v) => format!(" Did you mean '{}' ?", v),
                     None => String::new(),
                 };
-                write!(f, "Parameter '{}' is unknown.{}\n", param_name, suffix)
+                writeln!(f, "Parameter '{}' is unknown.{}", param_name, suffix)
             }
             CLIError::InvalidKeyValueSyntax(ref kv, is_hashmap) => {
                 let hashmap_info = if is_hashmap { "hashmap " } else { "" };
@@ -637,7 +619,7 @@ impl InvalidOptionsError {
     pub fn single(err: CLIError, exit_code: i32) -> InvalidOptionsError {
         InvalidOptionsError {
             issues: vec![err],
-            exit_code: exit_code,
+            exit_code,
         }
     }
 
@@ -649,9 +631,90 @@ impl InvalidOptionsError {
     }
 }
 
+/// The ways the program may obtain its access tokens.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AuthFlow {
+    /// Ask the user for consent in the browser, and store the tokens in the config directory
+    Installed,
+    /// Use a service account key file
+    ServiceAccount,
+    /// Ask the metadata server of the Google Cloud machine we run on
+    Metadata,
+    /// Find credentials like all Google Cloud client libraries do
+    ApplicationDefault,
+}
+
+impl AuthFlow {
+    pub fn variants() -> Vec<&'static str> {
+        vec![
+            "installed",
+            "service-account",
+            "metadata",
+            "application-default",
+        ]
+    }
+}
+
+impl AsRef<str> for AuthFlow {
+    fn as_ref(&self) -> &str {
+        match *self {
+            AuthFlow::Installed => "installed",
+            AuthFlow::ServiceAccount => "service-account",
+            AuthFlow::Metadata => "metadata",
+            AuthFlow::ApplicationDefault => "application-default",
+        }
+    }
+}
+
+impl FromStr for AuthFlow {
+    type Err = CLIError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "installed" => Ok(AuthFlow::Installed),
+            "service-account" => Ok(AuthFlow::ServiceAccount),
+            "metadata" => Ok(AuthFlow::Metadata),
+            "application-default" => Ok(AuthFlow::ApplicationDefault),
+            _ => Err(CLIError::Configuration(
+                ConfigurationError::UnknownAuthFlow(s.to_string()),
+            )),
+        }
+    }
+}
+
+/// Determine the authentication flow to use. Without an explicit flow, a given service account key
+/// implies the service-account flow.
+pub fn auth_flow_from_opts(
+    flow: Option<&str>,
+    service_account_key: Option<&str>,
+) -> Result<AuthFlow, CLIError> {
+    let flow = match flow {
+        Some(name) => AuthFlow::from_str(name)?,
+        None if service_account_key.is_some() => AuthFlow::ServiceAccount,
+        None => AuthFlow::Installed,
+    };
+    if flow == AuthFlow::ServiceAccount && service_account_key.is_none() {
+        return Err(CLIError::Configuration(
+            ConfigurationError::ServiceAccountKeyUnset,
+        ));
+    }
+    Ok(flow)
+}
+
+pub fn service_account_key_from_file(path: &str) -> Result<ServiceAccountKey, CLIError> {
+    let data = fs::read(path).map_err(|io_err| {
+        CLIError::Configuration(ConfigurationError::Io((path.to_string(), io_err)))
+    })?;
+    json::from_slice(&data).map_err(|json_err| {
+        CLIError::Configuration(ConfigurationError::Secret(
+            ApplicationSecretError::DecoderError((path.to_string(), json_err)),
+        ))
+    })
+}
+
 pub fn assure_config_dir_exists(dir: &str) -> Result<String, CLIError> {
     let trdir = dir.trim();
-    if trdir.len() == 0 {
+    if trdir.is_empty() {
         return Err(CLIError::Configuration(ConfigurationError::DirectoryUnset));
     }
 